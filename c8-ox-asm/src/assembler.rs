@@ -0,0 +1,709 @@
+use c8_ox_core::{Instruction, SymbolTable};
+use std::{collections::HashMap, error, fmt, fs, path::Path};
+
+// Where programs start unless ORG says otherwise
+const DEFAULT_ORIGIN: usize = 0x200;
+// Every mnemonic understood, the ones of Instruction::format with Syntax::Mnemonic
+const MNEMONICS: [&str; 30] = [
+    "ADD", "AND", "AUDIO", "CALL", "CLS", "DRW", "EXIT", "HIGH", "JP", "LD", "LOW", "OR", "PITCH",
+    "PLANE", "RET", "RND", "SCD", "SCL", "SCR", "SCU", "SE", "SHL", "SHR", "SKNP", "SKP", "SNE",
+    "SUB", "SUBN", "SYS", "XOR",
+];
+// Highest address ORG can move to
+const MAX_ADDRESS: i64 = 0xFFFF;
+// Limit for nested INCLUDEs, which also catches files including themselves
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+// An error in the source, with the position it was found at
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,   // Starting at 1
+    pub column: usize, // Starting at 1
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.message
+        )
+    }
+}
+
+impl error::Error for AsmError {}
+
+// Piece of a line together with its column
+#[derive(Clone, Debug)]
+struct Token {
+    text: String,
+    column: usize,
+}
+
+// A line of the source after splitting off comments and labels
+#[derive(Debug)]
+struct Line {
+    file: String,
+    number: usize,
+    label: Option<Token>,
+    mnemonic: Option<Token>,
+    operands: Vec<Token>,
+}
+
+impl Line {
+    fn error<S: Into<String>>(&self, column: usize, message: S) -> AsmError {
+        AsmError {
+            file: self.file.clone(),
+            line: self.number,
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn mnemonic(&self) -> String {
+        self.mnemonic
+            .as_ref()
+            .map(|mnemonic| mnemonic.text.to_uppercase())
+            .unwrap_or_default()
+    }
+
+    // Fails unless there are exactly count operands
+    fn expect_operands(&self, count: usize) -> Result<(), AsmError> {
+        if self.operands.len() == count {
+            return Ok(());
+        }
+        let column = self.mnemonic.as_ref().map_or(1, |mnemonic| mnemonic.column);
+        Err(self.error(
+            column,
+            format!(
+                "{} expects {} operands, found {}",
+                self.mnemonic(),
+                count,
+                self.operands.len()
+            ),
+        ))
+    }
+}
+
+// Operand after classifying it, expressions are evaluated once all labels are known
+#[derive(Clone, Debug, PartialEq)]
+enum Operand {
+    V(u8),
+    Range(u8, u8), // VX-VY
+    I,
+    IndirectI, // [I]
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    Long(String), // LONG NNNN
+    Expr(String),
+}
+
+fn register(text: &str) -> Option<u8> {
+    let text = text.trim();
+    let rest = text.strip_prefix('V').or_else(|| text.strip_prefix('v'))?;
+    if rest.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(rest, 16).ok()
+}
+
+fn classify(text: &str) -> Operand {
+    if let Some(x) = register(text) {
+        return Operand::V(x);
+    }
+    if let Some((x, y)) = text.split_once('-') {
+        if let (Some(x), Some(y)) = (register(x), register(y)) {
+            return Operand::Range(x, y);
+        }
+    }
+    let upper = text.to_uppercase();
+    if let Some(addr) = upper.strip_prefix("LONG ") {
+        return Operand::Long(text[text.len() - addr.len()..].trim().to_string());
+    }
+    match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::Dt,
+        "ST" => Operand::St,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "HF" => Operand::Hf,
+        "B" => Operand::B,
+        "R" => Operand::R,
+        _ => Operand::Expr(text.to_string()),
+    }
+}
+
+// Splits a line into label, mnemonic and operands
+fn parse_line(file: &str, number: usize, text: &str) -> Line {
+    // Comments run to the end of the line, unless the semicolon is part of a string
+    let mut in_string = false;
+    let mut end = text.len();
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ';' if !in_string => {
+                end = i;
+                break;
+            }
+            _ => {}
+        }
+    }
+    let text = &text[..end];
+
+    let mut line = Line {
+        file: file.to_string(),
+        number,
+        label: None,
+        mnemonic: None,
+        operands: Vec::new(),
+    };
+
+    let mut rest = text;
+    let mut offset = 0;
+    let next_word = |rest: &mut &str, offset: &mut usize| -> Option<Token> {
+        let trimmed = rest.trim_start();
+        *offset += rest.len() - trimmed.len();
+        if trimmed.is_empty() {
+            return None;
+        }
+        let len = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+        let token = Token {
+            text: trimmed[..len].to_string(),
+            column: *offset + 1,
+        };
+        *rest = &trimmed[len..];
+        *offset += len;
+        Some(token)
+    };
+
+    let mut word = next_word(&mut rest, &mut offset);
+    if let Some(label) = word.as_ref().filter(|word| word.text.ends_with(':')) {
+        line.label = Some(Token {
+            text: label.text.trim_end_matches(':').to_string(),
+            column: label.column,
+        });
+        word = next_word(&mut rest, &mut offset);
+    }
+    line.mnemonic = word;
+
+    // Operands are separated by commas
+    if !rest.trim().is_empty() {
+        for operand in rest.split(',') {
+            let trimmed = operand.trim_start();
+            let column = offset + operand.len() - trimmed.len() + 1;
+            line.operands.push(Token {
+                text: trimmed.trim_end().to_string(),
+                column,
+            });
+            offset += operand.len() + 1;
+        }
+    }
+
+    // NAME EQU VALUE defines a constant, the name is kept as label
+    if let (None, Some(name)) = (&line.label, &line.mnemonic) {
+        let first = line.operands.first().map(|operand| operand.text.clone());
+        if let Some((equ, value)) = first.as_deref().and_then(|first| first.split_once(' ')) {
+            if equ.eq_ignore_ascii_case("EQU") {
+                let column = line.operands[0].column + equ.len() + 1;
+                line.label = Some(name.clone());
+                line.mnemonic = Some(Token {
+                    text: "EQU".to_string(),
+                    column: line.operands[0].column,
+                });
+                line.operands[0] = Token {
+                    text: value.trim().to_string(),
+                    column: column + value.len() - value.trim_start().len(),
+                };
+            }
+        }
+    }
+
+    line
+}
+
+// Reads a file and the ones it includes into a flat list of lines
+fn read_lines(
+    path: &Path,
+    depth: usize,
+    included_from: Option<(&Line, usize)>,
+    lines: &mut Vec<Line>,
+) -> Result<(), AsmError> {
+    let file = path.display().to_string();
+    let source = fs::read_to_string(path).map_err(|error| match included_from {
+        Some((line, column)) => line.error(column, format!("unable to read {}: {}", file, error)),
+        None => AsmError {
+            file: file.clone(),
+            line: 0,
+            column: 0,
+            message: format!("unable to read file: {}", error),
+        },
+    })?;
+
+    for (i, text) in source.lines().enumerate() {
+        let line = parse_line(&file, i + 1, text);
+        if line.mnemonic() == "INCLUDE" {
+            line.expect_operands(1)?;
+            let operand = &line.operands[0];
+            let name = operand.text.trim_matches('"');
+            if depth >= MAX_INCLUDE_DEPTH {
+                return Err(line.error(operand.column, "includes are nested too deep"));
+            }
+            let included = path.parent().unwrap_or_else(|| Path::new("")).join(name);
+            read_lines(&included, depth + 1, Some((&line, operand.column)), lines)?;
+        } else {
+            lines.push(line);
+        }
+    }
+    Ok(())
+}
+
+// Labels, and constants whose values are evaluated on first use
+struct Symbols {
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, (String, usize)>, // Expression and index of the defining line
+}
+
+impl Symbols {
+    // Number literals (#1F, 0x1F, $1F, %1010, 31) and symbols, combined with + and -
+    fn evaluate(
+        &self,
+        lines: &[Line],
+        line: &Line,
+        token: &Token,
+        depth: usize,
+    ) -> Result<i64, AsmError> {
+        let mut value = 0;
+        let mut rest = token.text.as_str();
+        let mut column = token.column;
+        let mut sign = 1;
+        loop {
+            // Signs in front of the term
+            loop {
+                let trimmed = rest.trim_start();
+                column += rest.len() - trimmed.len();
+                rest = trimmed;
+                match rest.chars().next() {
+                    Some('-') => sign = -sign,
+                    Some('+') => {}
+                    _ => break,
+                }
+                rest = &rest[1..];
+                column += 1;
+            }
+
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            let term = rest[..end].trim_end();
+            if term.is_empty() {
+                return Err(line.error(column, "expected a value"));
+            }
+            value += sign * self.term(lines, line, term, column, depth)?;
+            column += end;
+            rest = &rest[end..];
+
+            // The operator in front of the next term
+            match rest.chars().next() {
+                Some(operator) => {
+                    sign = if operator == '-' { -1 } else { 1 };
+                    rest = &rest[1..];
+                    column += 1;
+                }
+                None => break,
+            }
+        }
+        Ok(value)
+    }
+
+    fn term(
+        &self,
+        lines: &[Line],
+        line: &Line,
+        term: &str,
+        column: usize,
+        depth: usize,
+    ) -> Result<i64, AsmError> {
+        let number = if let Some(hex) = term.strip_prefix('#').or_else(|| term.strip_prefix('$')) {
+            i64::from_str_radix(hex, 16).ok()
+        } else if let Some(hex) = term.strip_prefix("0x").or_else(|| term.strip_prefix("0X")) {
+            i64::from_str_radix(hex, 16).ok()
+        } else if let Some(binary) = term.strip_prefix('%') {
+            i64::from_str_radix(binary, 2).ok()
+        } else if term.starts_with(|c: char| c.is_ascii_digit()) {
+            term.parse().ok()
+        } else if let Some(&addr) = self.labels.get(term) {
+            Some(addr as i64)
+        } else if let Some((expression, index)) = self.constants.get(term) {
+            if depth > 16 {
+                return Err(line.error(
+                    column,
+                    format!("constant {} is defined in terms of itself", term),
+                ));
+            }
+            let defining = &lines[*index];
+            let token = Token {
+                text: expression.clone(),
+                column: defining.operands[0].column,
+            };
+            return self.evaluate(lines, defining, &token, depth + 1);
+        } else {
+            return Err(line.error(column, format!("unknown symbol {}", term)));
+        };
+        number.ok_or_else(|| line.error(column, format!("invalid number {}", term)))
+    }
+}
+
+// Size in bytes of the output of a line, known before the labels are
+fn size(line: &Line) -> usize {
+    match line.mnemonic().as_str() {
+        "" | "EQU" | "ORG" => 0,
+        "DB" => line.operands.len(),
+        "DW" => line.operands.len() * 2,
+        "LD" if line
+            .operands
+            .get(1)
+            .is_some_and(|operand| matches!(classify(&operand.text), Operand::Long(_))) =>
+        {
+            4
+        }
+        _ => 2,
+    }
+}
+
+// Assembles a source file, with INCLUDEs resolved relative to the including file.
+// The result is the ROM image, starting at the origin (0x200 unless changed by a leading ORG),
+// together with the labels and the source line of every instruction and data directive.
+pub fn assemble(path: &Path) -> Result<(Vec<u8>, SymbolTable), AsmError> {
+    let mut lines = Vec::new();
+    read_lines(path, 0, None, &mut lines)?;
+
+    // First pass: addresses of the labels
+    let mut symbols = Symbols {
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+    };
+    let mut origin = None;
+    let mut addr = DEFAULT_ORIGIN;
+    for (index, line) in lines.iter().enumerate() {
+        if let Some(label) = &line.label {
+            if symbols.labels.contains_key(&label.text)
+                || symbols.constants.contains_key(&label.text)
+            {
+                return Err(line.error(label.column, format!("{} is defined twice", label.text)));
+            }
+            if line.mnemonic() == "EQU" {
+                line.expect_operands(1)?;
+                symbols
+                    .constants
+                    .insert(label.text.clone(), (line.operands[0].text.clone(), index));
+            } else {
+                symbols.labels.insert(label.text.clone(), addr);
+            }
+        }
+        if line.mnemonic() == "ORG" {
+            line.expect_operands(1)?;
+            let target = org_target(&symbols, &lines, line)?;
+            if origin.is_some() && target < addr {
+                return Err(line.error(line.operands[0].column, "ORG cannot move backwards"));
+            }
+            if origin.is_none() {
+                origin = Some(target);
+            }
+            addr = target;
+        }
+        if size(line) > 0 && origin.is_none() {
+            origin = Some(DEFAULT_ORIGIN);
+        }
+        addr += size(line);
+    }
+    let origin = origin.unwrap_or(DEFAULT_ORIGIN);
+
+    // Second pass: the output
+    let mut rom = Vec::new();
+    let mut table = SymbolTable::new();
+    let mut labels: Vec<_> = symbols.labels.iter().collect();
+    labels.sort();
+    for (name, &addr) in labels {
+        table.add_label(addr, name);
+    }
+    // Source files by absolute path, so debuggers find them from any working directory
+    let mut files = HashMap::new();
+    for line in lines.iter() {
+        if size(line) > 0 {
+            let file = files.entry(line.file.as_str()).or_insert_with(|| {
+                fs::canonicalize(&line.file)
+                    .map_or_else(|_| line.file.clone(), |path| path.display().to_string())
+            });
+            table.add_line(origin + rom.len(), file, line.number);
+        }
+        let value = |index: usize, max: i64| -> Result<u16, AsmError> {
+            let mut token = line.operands[index].clone();
+            if let Operand::Long(addr) = classify(&token.text) {
+                token.column += token.text.len() - addr.len();
+                token.text = addr;
+            }
+            let value = symbols.evaluate(&lines, line, &token, 0)?;
+            // Negative bytes are accepted as two's complement
+            if value > max || value < -(max + 1) / 2 {
+                return Err(line.error(
+                    token.column,
+                    format!("{} is out of range, the maximum is {:#X}", value, max),
+                ));
+            }
+            Ok((value & max) as u16)
+        };
+
+        match line.mnemonic().as_str() {
+            "" | "EQU" => {}
+            "ORG" => {
+                let target = org_target(&symbols, &lines, line)?;
+                rom.resize(target - origin, 0);
+            }
+            "DB" => {
+                for i in 0..line.operands.len() {
+                    rom.push(value(i, 0xFF)? as u8);
+                }
+            }
+            "DW" => {
+                for i in 0..line.operands.len() {
+                    rom.extend_from_slice(&value(i, 0xFFFF)?.to_be_bytes());
+                }
+            }
+            _ => {
+                let instruction = instruction(line, &value)?;
+                for opcode in instruction.encode() {
+                    rom.extend_from_slice(&opcode.to_be_bytes());
+                }
+            }
+        }
+    }
+    Ok((rom, table))
+}
+
+// The address an ORG line moves to, which has to be inside the 64 KiB of XO-CHIP
+fn org_target(symbols: &Symbols, lines: &[Line], line: &Line) -> Result<usize, AsmError> {
+    let operand = &line.operands[0];
+    let target = symbols.evaluate(lines, line, operand, 0)?;
+    if !(0..=MAX_ADDRESS).contains(&target) {
+        return Err(line.error(
+            operand.column,
+            format!(
+                "ORG {:#X} is outside the memory, the maximum is {:#X}",
+                target, MAX_ADDRESS
+            ),
+        ));
+    }
+    Ok(target as usize)
+}
+
+// Builds the instruction of a line, value(index, max) evaluates the operand at index
+fn instruction<V>(line: &Line, value: &V) -> Result<Instruction, AsmError>
+where
+    V: Fn(usize, i64) -> Result<u16, AsmError>,
+{
+    use Instruction::*;
+    use Operand::*;
+
+    let mnemonic = line.mnemonic();
+    let operands: Vec<Operand> = line
+        .operands
+        .iter()
+        .map(|operand| classify(&operand.text))
+        .collect();
+
+    let instruction = match (mnemonic.as_str(), operands.as_slice()) {
+        ("CLS", []) => Clear,
+        ("RET", []) => Return,
+        ("SCR", []) => ScrollRight,
+        ("SCL", []) => ScrollLeft,
+        ("EXIT", []) => Exit,
+        ("LOW", []) => LowRes,
+        ("HIGH", []) => HighRes,
+        ("AUDIO", []) => LoadAudio,
+        ("SCD", [Expr(_)]) => ScrollDown(value(0, 0xF)? as u8),
+        ("SCU", [Expr(_)]) => ScrollUp(value(0, 0xF)? as u8),
+        ("SYS", [Expr(_)]) => MachineCall(value(0, 0xFFF)?),
+        ("JP", [Expr(_)]) => Jump(value(0, 0xFFF)?),
+        ("JP", [V(0), Expr(_)]) => JumpOffset(value(1, 0xFFF)?),
+        ("CALL", [Expr(_)]) => Call(value(0, 0xFFF)?),
+        ("SE", [V(x), Expr(_)]) => SkipEqualByte(*x, value(1, 0xFF)? as u8),
+        ("SE", [V(x), V(y)]) => SkipEqual(*x, *y),
+        ("SNE", [V(x), Expr(_)]) => SkipNotEqualByte(*x, value(1, 0xFF)? as u8),
+        ("SNE", [V(x), V(y)]) => SkipNotEqual(*x, *y),
+        ("LD", [IndirectI, Range(x, y)]) => StoreRange(*x, *y),
+        ("LD", [Range(x, y), IndirectI]) => LoadRange(*x, *y),
+        ("LD", [V(x), Expr(_)]) => LoadByte(*x, value(1, 0xFF)? as u8),
+        ("LD", [V(x), V(y)]) => Move(*x, *y),
+        ("LD", [I, Expr(_)]) => LoadI(value(1, 0xFFF)?),
+        ("LD", [I, Long(_)]) => LoadLongI(value(1, 0xFFFF)?),
+        ("LD", [V(x), Dt]) => GetDelay(*x),
+        ("LD", [V(x), K]) => WaitKey(*x),
+        ("LD", [Dt, V(x)]) => SetDelay(*x),
+        ("LD", [St, V(x)]) => SetSound(*x),
+        ("LD", [F, V(x)]) => Font(*x),
+        ("LD", [Hf, V(x)]) => BigFont(*x),
+        ("LD", [B, V(x)]) => Bcd(*x),
+        ("LD", [IndirectI, V(x)]) => Store(*x),
+        ("LD", [V(x), IndirectI]) => Load(*x),
+        ("LD", [R, V(x)]) => StoreFlags(*x),
+        ("LD", [V(x), R]) => LoadFlags(*x),
+        ("ADD", [V(x), Expr(_)]) => AddByte(*x, value(1, 0xFF)? as u8),
+        ("ADD", [V(x), V(y)]) => Add(*x, *y),
+        ("ADD", [I, V(x)]) => AddI(*x),
+        ("OR", [V(x), V(y)]) => Or(*x, *y),
+        ("AND", [V(x), V(y)]) => And(*x, *y),
+        ("XOR", [V(x), V(y)]) => Xor(*x, *y),
+        ("SUB", [V(x), V(y)]) => Sub(*x, *y),
+        ("SUBN", [V(x), V(y)]) => SubReverse(*x, *y),
+        ("SHR", [V(x)]) => ShiftRight(*x, *x),
+        ("SHR", [V(x), V(y)]) => ShiftRight(*x, *y),
+        ("SHL", [V(x)]) => ShiftLeft(*x, *x),
+        ("SHL", [V(x), V(y)]) => ShiftLeft(*x, *y),
+        ("RND", [V(x), Expr(_)]) => Random(*x, value(1, 0xFF)? as u8),
+        ("DRW", [V(x), V(y), Expr(_)]) => Draw(*x, *y, value(2, 0xF)? as u8),
+        ("SKP", [V(x)]) => SkipKey(*x),
+        ("SKNP", [V(x)]) => SkipNotKey(*x),
+        ("PLANE", [Expr(_)]) => SelectPlanes(value(0, 0xF)? as u8),
+        ("PITCH", [V(x)]) => SetPitch(*x),
+        _ => {
+            let column = line.mnemonic.as_ref().map_or(1, |mnemonic| mnemonic.column);
+            let message = if MNEMONICS.contains(&mnemonic.as_str()) {
+                let operands: Vec<&str> = line.operands.iter().map(|o| o.text.as_str()).collect();
+                format!("invalid operands for {}: {}", mnemonic, operands.join(", "))
+            } else {
+                format!("unknown instruction {}", mnemonic)
+            };
+            return Err(line.error(column, message));
+        }
+    };
+    Ok(instruction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use c8_ox_core::{decode, decode_at, Syntax};
+    use std::{env, process};
+
+    // Writes the files into a directory of their own and assembles the first one
+    fn assemble_files(name: &str, files: &[(&str, &str)]) -> Result<Vec<u8>, AsmError> {
+        let dir = env::temp_dir().join(format!("c8-ox-asm-{}-{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (file, source) in files {
+            fs::write(dir.join(file), source).unwrap();
+        }
+        let result = assemble(&dir.join(files[0].0)).map(|(rom, _)| rom);
+        fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    fn assemble_source(name: &str, source: &str) -> Result<Vec<u8>, AsmError> {
+        assemble_files(name, &[("main.asm", source)])
+    }
+
+    // The line and column of the error the source fails with
+    fn error_at(name: &str, source: &str) -> (usize, usize, String) {
+        let error = assemble_source(name, source).unwrap_err();
+        (error.line, error.column, error.message)
+    }
+
+    #[test]
+    fn disassembly_assembles_to_the_same_instructions() {
+        // Every opcode the disassembler prints as an instruction, and a long address
+        let mut instructions: Vec<Instruction> = (0..=0xFFFF)
+            .map(decode)
+            .filter(|instruction| {
+                !matches!(
+                    instruction,
+                    Instruction::Unknown(_) | Instruction::LoadLongI(_)
+                )
+            })
+            .collect();
+        instructions.push(Instruction::LoadLongI(0xBEEF));
+        let source: Vec<String> = instructions
+            .iter()
+            .map(|instruction| format!("    {}", instruction.format(Syntax::Mnemonic, &|_| None)))
+            .collect();
+
+        let rom = assemble_source("round-trip", &source.join("\n")).unwrap();
+        let mut addr = 0;
+        for instruction in instructions.iter() {
+            assert_eq!(decode_at(&rom, addr), Some(*instruction));
+            addr += instruction.size();
+        }
+        assert_eq!(addr, rom.len());
+    }
+
+    #[test]
+    fn labels_and_constants() {
+        let source = "\
+SPEED EQU 4
+LIMIT EQU SPEED + 2
+start:  LD V0, SPEED
+        SE V0, LIMIT - 1
+        JP start
+        LD I, sprite
+sprite: DB %10000001, $FF
+";
+        let rom = assemble_source("labels", source).unwrap();
+        assert_eq!(
+            rom,
+            [0x60, 0x04, 0x30, 0x05, 0x12, 0x00, 0xA2, 0x08, 0x81, 0xFF]
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_operand() {
+        assert_eq!(
+            error_at("range", "    CLS\n    LD V0, #100\n"),
+            (
+                2,
+                12,
+                "256 is out of range, the maximum is 0xFF".to_string()
+            )
+        );
+        assert_eq!(error_at("symbol", "  JP nowhere\n").0, 1);
+        assert_eq!(error_at("symbol", "  JP nowhere\n").1, 6);
+        assert_eq!(error_at("mnemonic", "\n\n  FOO V0\n").0, 3);
+        assert_eq!(error_at("mnemonic", "\n\n  FOO V0\n").1, 3);
+        assert_eq!(error_at("twice", "a: CLS\na: CLS\n").0, 2);
+        assert_eq!(error_at("self", "A EQU B\nB EQU A\n  LD V0, A\n").0, 1);
+    }
+
+    #[test]
+    fn org_moves_forward_inside_the_memory() {
+        let rom = assemble_source("org", "  ORG 0x300\nloop: JP loop\n  ORG loop + 4\n  CLS\n");
+        assert_eq!(rom.unwrap(), [0x13, 0x00, 0x00, 0x00, 0x00, 0xE0]);
+
+        assert_eq!(error_at("org-negative", "  ORG -5\n").1, 7);
+        assert_eq!(error_at("org-large", "  CLS\n  ORG 0x10000000000\n").0, 2);
+        assert_eq!(error_at("org-large", "  CLS\n  ORG 0x10000000000\n").1, 7);
+        assert_eq!(
+            error_at("org-back", "  ORG 0x300\n  CLS\n  ORG 0x200\n").2,
+            "ORG cannot move backwards"
+        );
+    }
+
+    #[test]
+    fn includes() {
+        let files = [
+            ("main.asm", "  CALL draw\n  INCLUDE \"draw.asm\"\n"),
+            ("draw.asm", "draw: CLS\n  RET\n"),
+        ];
+        assert_eq!(
+            assemble_files("include", &files).unwrap(),
+            [0x22, 0x02, 0x00, 0xE0, 0x00, 0xEE]
+        );
+
+        let error = assemble_files("recursion", &[("main.asm", "  INCLUDE \"main.asm\"\n")]);
+        let error = error.unwrap_err();
+        assert_eq!((error.line, error.column), (1, 11));
+        assert_eq!(error.message, "includes are nested too deep");
+    }
+}
@@ -0,0 +1,63 @@
+mod assembler;
+
+use std::{env, fs, path::PathBuf, process};
+
+const USAGE: &str = "Usage: c8-ox-asm [-o <output-path>] [--symbols <symbols-path>] <source-path>";
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut source = None;
+    let mut output = None;
+    let mut symbols_path = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" => match iter.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("-o expects a file path");
+                    process::exit(1);
+                }
+            },
+            "--symbols" => match iter.next() {
+                Some(path) => symbols_path = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("--symbols expects a file path");
+                    process::exit(1);
+                }
+            },
+            _ if arg.starts_with('-') => {
+                eprintln!("Unknown option {}\n{}", arg, USAGE);
+                process::exit(1);
+            }
+            _ => source = Some(PathBuf::from(arg)),
+        }
+    }
+    let source = source.unwrap_or_else(|| {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    });
+    // Next to the source by default, e.g. test.asm becomes test.ch8
+    let output = output.unwrap_or_else(|| source.with_extension("ch8"));
+
+    let (rom, symbols) = match assembler::assemble(&source) {
+        Ok(assembled) => assembled,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+    if let Err(error) = fs::write(&output, &rom) {
+        eprintln!("Unable to write {}: {}", output.display(), error);
+        process::exit(1);
+    }
+    println!("Wrote {} byte to {}", rom.len(), output.display());
+    if let Some(path) = symbols_path {
+        if let Err(error) = fs::write(&path, symbols.to_text()) {
+            eprintln!("Unable to write {}: {}", path.display(), error);
+            process::exit(1);
+        }
+        println!("Wrote symbols to {}", path.display());
+    }
+}
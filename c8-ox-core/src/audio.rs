@@ -0,0 +1,72 @@
+use crate::snapshot::{Reader, SnapshotError, Writer};
+
+// Number of 1-bit samples in an XO-CHIP audio pattern
+pub const PATTERN_BITS: usize = 128;
+
+// Pattern played before a ROM loads its own one, a square wave (500 Hz at the default pitch)
+const DEFAULT_PATTERN: [u8; PATTERN_BITS / 8] = [0xF0; PATTERN_BITS / 8];
+// Pitch register value that plays the pattern at 4000 bits per second
+const DEFAULT_PITCH: u8 = 64;
+
+// XO-CHIP sound output: a 128-bit 1-bit pattern, loaded by F002, played at a rate set by FX3A.
+// It is looped for as long as the sound timer is active. Earlier platforms only ever play the
+// default pattern, which makes a plain beep.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Audio {
+    pattern: [u8; PATTERN_BITS / 8], // Most significant bit of the first byte is played first
+    pitch: u8,                       //
+}
+
+impl Audio {
+    pub fn new() -> Audio {
+        Audio {
+            pattern: DEFAULT_PATTERN,
+            pitch: DEFAULT_PITCH,
+        }
+    }
+
+    pub fn pattern(&self) -> &[u8; PATTERN_BITS / 8] {
+        &self.pattern
+    }
+
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    // Playback rate of the pattern in bits per second
+    pub fn rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    // Level (0 or 1) of the given bit of the pattern
+    pub fn sample(&self, bit: usize) -> u8 {
+        let bit = bit % PATTERN_BITS;
+        (self.pattern[bit / 8] >> (7 - bit % 8)) & 0x1
+    }
+
+    pub(crate) fn set_pattern(&mut self, pattern: &[u8]) {
+        self.pattern.copy_from_slice(pattern);
+    }
+
+    pub(crate) fn set_pitch(&mut self, pitch: u8) {
+        self.pitch = pitch;
+    }
+
+    pub(crate) fn save(&self, writer: &mut Writer) {
+        writer.bytes(&self.pattern);
+        writer.u8(self.pitch);
+    }
+
+    pub(crate) fn load(reader: &mut Reader) -> Result<Audio, SnapshotError> {
+        let mut audio = Audio::new();
+        audio.set_pattern(reader.bytes(PATTERN_BITS / 8)?);
+        audio.set_pitch(reader.u8()?);
+        Ok(audio)
+    }
+}
+
+impl Default for Audio {
+    fn default() -> Audio {
+        Audio::new()
+    }
+}
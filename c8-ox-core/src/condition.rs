@@ -0,0 +1,236 @@
+use crate::schip8::SChip8;
+use std::fmt;
+
+// Value compared by a condition
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    V(usize),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+    Memory(usize), // Byte at the address, written as [addr]
+    Number(usize),
+}
+
+impl Operand {
+    fn parse(text: &str) -> Option<Operand> {
+        let text = text.trim().to_uppercase();
+        let operand = match text.as_str() {
+            "I" => Operand::I,
+            "PC" => Operand::Pc,
+            "SP" => Operand::Sp,
+            "DT" => Operand::Dt,
+            "ST" => Operand::St,
+            _ if text.starts_with('[') && text.ends_with(']') => {
+                Operand::Memory(parse_number(&text[1..text.len() - 1])?)
+            }
+            _ if text.len() == 2 && text.starts_with('V') => {
+                Operand::V(usize::from_str_radix(&text[1..], 16).ok()?)
+            }
+            _ => Operand::Number(parse_number(&text)?),
+        };
+        Some(operand)
+    }
+
+    fn value(&self, schip8: &SChip8) -> Option<usize> {
+        let value = match *self {
+            Operand::V(x) => schip8.v(x) as usize,
+            Operand::I => schip8.i() as usize,
+            Operand::Pc => schip8.pc(),
+            Operand::Sp => schip8.sp(),
+            Operand::Dt => schip8.dt() as usize,
+            Operand::St => schip8.st() as usize,
+            Operand::Memory(addr) => *schip8.memory().get(addr)? as usize,
+            Operand::Number(number) => number,
+        };
+        Some(value)
+    }
+}
+
+// Hexadecimal, with or without 0x or # in front
+fn parse_number(text: &str) -> Option<usize> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0X")
+        .or_else(|| text.strip_prefix('#'))
+        .unwrap_or(text);
+    usize::from_str_radix(digits, 16).ok()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Comparison {
+    left: Operand,
+    operator: &'static str,
+    right: Operand,
+}
+
+// Longer operators first, so <= is not taken for <
+const OPERATORS: [&str; 6] = ["==", "!=", "<=", ">=", "<", ">"];
+
+impl Comparison {
+    fn parse(text: &str) -> Option<Comparison> {
+        let operator = OPERATORS.iter().find(|operator| text.contains(*operator))?;
+        let (left, right) = text.split_once(operator)?;
+        Some(Comparison {
+            left: Operand::parse(left)?,
+            operator,
+            right: Operand::parse(right)?,
+        })
+    }
+
+    fn holds(&self, schip8: &SChip8) -> bool {
+        let (left, right) = match (self.left.value(schip8), self.right.value(schip8)) {
+            (Some(left), Some(right)) => (left, right),
+            _ => return false,
+        };
+        match self.operator {
+            "==" => left == right,
+            "!=" => left != right,
+            "<=" => left <= right,
+            ">=" => left >= right,
+            "<" => left < right,
+            _ => left > right,
+        }
+    }
+}
+
+// Condition of a breakpoint like "V3 == 10 && DT == 0", comparing registers, bytes in memory
+// written as [addr] and hexadecimal numbers. && binds stronger than ||, there are no parentheses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+    text: String,
+    alternatives: Vec<Vec<Comparison>>, // Joined by ||, each consisting of comparisons joined by &&
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Option<Condition> {
+        let alternatives = text
+            .split("||")
+            .map(|alternative| alternative.split("&&").map(Comparison::parse).collect())
+            .collect::<Option<Vec<Vec<Comparison>>>>()?;
+        Some(Condition {
+            text: text.trim().to_string(),
+            alternatives,
+        })
+    }
+
+    pub fn holds(&self, schip8: &SChip8) -> bool {
+        self.alternatives
+            .iter()
+            .any(|comparisons| comparisons.iter().all(|c| c.holds(schip8)))
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::Platform;
+    use crate::random::SeededRandom;
+    use crate::rom::Rom;
+
+    fn machine() -> SChip8 {
+        let rom = Rom::new(vec![0x12, 0x00]).unwrap();
+        SChip8::new(&rom, &Platform::default(), Box::new(SeededRandom::new(1))).unwrap()
+    }
+
+    fn holds(text: &str, schip8: &SChip8) -> bool {
+        Condition::parse(text).unwrap().holds(schip8)
+    }
+
+    #[test]
+    fn compares_registers_and_numbers() {
+        let mut schip8 = machine();
+        schip8.set_v(3, 0x10);
+        assert!(holds("V3 == 0x10 && dt == 0", &schip8));
+        schip8.set_dt(1);
+        assert!(!holds("V3 == 0x10 && dt == 0", &schip8));
+
+        // Bare numbers are hexadecimal, names are case insensitive
+        assert!(holds("v3 == 10", &schip8));
+        assert!(holds("V3 == #10", &schip8));
+        assert!(holds("DT==1", &schip8));
+        schip8.set_v(15, 0xAB);
+        assert!(holds("vf == ab", &schip8));
+        assert!(holds("pc == 200 && i == 0 && sp == 0 && st == 0", &schip8));
+    }
+
+    #[test]
+    fn finds_the_longest_operator() {
+        let mut schip8 = machine();
+        schip8.set_v(3, 5);
+        for (text, expected) in [
+            ("V3 == 5", true),
+            ("V3 != 5", false),
+            ("V3 <= 5", true),
+            ("V3 >= 5", true),
+            ("V3 < 5", false),
+            ("V3 > 5", false),
+            ("V3 < 6", true),
+            ("V3 > 4", true),
+        ]
+        .iter()
+        {
+            let condition = Condition::parse(text).unwrap();
+            assert_eq!(condition.holds(&schip8), *expected, "{}", text);
+        }
+        let condition = Condition::parse("V3 <= 5").unwrap();
+        assert_eq!(condition.alternatives[0][0].operator, "<=");
+        assert_eq!(condition.alternatives[0][0].right, Operand::Number(5));
+    }
+
+    #[test]
+    fn and_binds_stronger_than_or() {
+        let mut schip8 = machine();
+        schip8.set_v(0, 1);
+        // (V0 == 1) || (V1 == 1 && V2 == 1)
+        assert!(holds("V0 == 1 || V1 == 1 && V2 == 1", &schip8));
+        // (V1 == 1 && V2 == 1) || (V0 == 1)
+        assert!(holds("V1 == 1 && V2 == 1 || V0 == 1", &schip8));
+        // (V0 == 1 && V1 == 1) || (V2 == 1), false if || bound stronger
+        assert!(!holds("V0 == 1 && V1 == 1 || V2 == 1", &schip8));
+        assert!(holds("V0 == 1 && V1 == 0 || V2 == 1", &schip8));
+    }
+
+    #[test]
+    fn reads_memory() {
+        let mut schip8 = machine();
+        schip8.memory_mut()[0x300] = 0x42;
+        assert!(holds("[300] == 42", &schip8));
+        assert!(holds("[0x300] == 42", &schip8));
+        assert!(holds("[#300] != 0", &schip8));
+        // Outside the memory nothing holds
+        assert!(!holds("[FFFFF] == 0", &schip8));
+        assert!(!holds("[FFFFF] != 0", &schip8));
+    }
+
+    #[test]
+    fn rejects_invalid_conditions() {
+        for text in [
+            "V3 = 1",
+            "V10 == 1",
+            "",
+            "V3 ==",
+            "== 1",
+            "V3 == 1 &&",
+            "X == 1",
+        ]
+        .iter()
+        {
+            assert_eq!(Condition::parse(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn displays_the_trimmed_text() {
+        let condition = Condition::parse("  V3 == 10 && dt == 0 ").unwrap();
+        assert_eq!(condition.to_string(), "V3 == 10 && dt == 0");
+    }
+}
@@ -0,0 +1,202 @@
+use crate::hooks::{Access, Hooks};
+use crate::instruction::decode_at;
+use crate::schip8::SChip8;
+use std::ops::Range;
+use std::{error, fmt};
+
+// Bytes per row of the memory map, see Coverage::memory_map
+pub const MEMORY_MAP_WIDTH: usize = 64;
+
+// Colours of the memory map as 0xRRGGBB. Bytes used in several ways get the mix of their colours.
+const UNUSED_MEMORY_COLOR: u32 = 0x101010;
+const UNUSED_PROGRAM_COLOR: u32 = 0x505050;
+const EXECUTED_COLOR: u32 = 0x30C030;
+const SPRITE_COLOR: u32 = 0x3060F0;
+const READ_COLOR: u32 = 0xE0C020;
+const WRITTEN_COLOR: u32 = 0xE03030;
+
+// Flags of a byte, see ByteUse
+const EXECUTED: u8 = 0x1;
+const SPRITE: u8 = 0x2;
+const READ: u8 = 0x4;
+const WRITTEN: u8 = 0x8;
+
+// The flags with their names in the coverage file and colours on the memory map
+const KINDS: [(u8, &str, u32); 4] = [
+    (EXECUTED, "executed", EXECUTED_COLOR),
+    (SPRITE, "sprite", SPRITE_COLOR),
+    (READ, "read", READ_COLOR),
+    (WRITTEN, "written", WRITTEN_COLOR),
+];
+
+// A line of a coverage file that could not be parsed, starting at 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CoverageError {
+    pub line: usize,
+}
+
+impl fmt::Display for CoverageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid coverage file entry in line {}", self.line)
+    }
+}
+
+impl error::Error for CoverageError {}
+
+// How a byte of memory was used while the program ran
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ByteUse {
+    pub executed: bool, // Part of an executed instruction
+    pub sprite: bool,   // Drawn by DXYN
+    pub read: bool,     // Read by FX65, 5XY3 or F002
+    pub written: bool,  // Written by FX33, FX55 or 5XY2
+}
+
+impl ByteUse {
+    // Read but never executed, so most likely data between the code
+    pub fn is_data(&self) -> bool {
+        !self.executed && (self.sprite || self.read)
+    }
+}
+
+// Records which bytes of memory were executed, read and written. Run the machine with
+// SChip8::run_frame_with(key, &mut coverage), then save it with to_text or draw it with memory_map.
+// The file has one range of addresses per line, hexadecimal and inclusive, and lines starting with
+// ; are comments:
+//   executed 200 2F1
+//   sprite 2F2 30B
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Coverage {
+    bytes: Vec<u8>, // Flags by address, up to the highest address used
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Coverage, CoverageError> {
+        let mut coverage = Coverage::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let error = CoverageError { line: i + 1 };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (kind, start, end) = match fields.as_slice() {
+                [kind, start, end] => (kind, start, end),
+                _ => return Err(error),
+            };
+            let flag = KINDS
+                .iter()
+                .find(|(_, name, _)| name == kind)
+                .map(|(flag, _, _)| *flag)
+                .ok_or(error)?;
+            let start = usize::from_str_radix(start, 16).map_err(|_| error)?;
+            let end = usize::from_str_radix(end, 16).map_err(|_| error)?;
+            if end < start {
+                return Err(error);
+            }
+            coverage.mark(start, end + 1 - start, flag);
+        }
+        Ok(coverage)
+    }
+
+    fn mark(&mut self, addr: usize, len: usize, flag: u8) {
+        if addr + len > self.bytes.len() {
+            self.bytes.resize(addr + len, 0);
+        }
+        for byte in self.bytes[addr..addr + len].iter_mut() {
+            *byte |= flag;
+        }
+    }
+
+    pub fn at(&self, addr: usize) -> ByteUse {
+        let flags = self.bytes.get(addr).copied().unwrap_or(0);
+        ByteUse {
+            executed: flags & EXECUTED != 0,
+            sprite: flags & SPRITE != 0,
+            read: flags & READ != 0,
+            written: flags & WRITTEN != 0,
+        }
+    }
+
+    // Number of bytes in the range that were used at all
+    pub fn used(&self, range: Range<usize>) -> usize {
+        range
+            .filter(|&addr| self.at(addr) != ByteUse::default())
+            .count()
+    }
+
+    // The file format read by Coverage::parse
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (flag, name, _) in KINDS.iter() {
+            let mut addr = 0;
+            while addr < self.bytes.len() {
+                if self.bytes[addr] & flag == 0 {
+                    addr += 1;
+                    continue;
+                }
+                let start = addr;
+                while addr < self.bytes.len() && self.bytes[addr] & flag != 0 {
+                    addr += 1;
+                }
+                text += &format!("{} {:03X} {:03X}\n", name, start, addr - 1);
+            }
+        }
+        text
+    }
+
+    // One colour per byte of a memory of the given size, MEMORY_MAP_WIDTH bytes per row. Unused bytes
+    // of the program are lighter than the rest of the unused memory.
+    pub fn memory_map(&self, size: usize, program: Range<usize>) -> Vec<u32> {
+        (0..size)
+            .map(|addr| {
+                let flags = self.bytes.get(addr).copied().unwrap_or(0);
+                let colors: Vec<u32> = KINDS
+                    .iter()
+                    .filter(|(flag, _, _)| flags & flag != 0)
+                    .map(|(_, _, color)| *color)
+                    .collect();
+                if !colors.is_empty() {
+                    mix(&colors)
+                } else if program.contains(&addr) {
+                    UNUSED_PROGRAM_COLOR
+                } else {
+                    UNUSED_MEMORY_COLOR
+                }
+            })
+            .collect()
+    }
+}
+
+// The average of the colours
+fn mix(colors: &[u32]) -> u32 {
+    let channel = |shift: u32| {
+        let sum: u32 = colors.iter().map(|color| (color >> shift) & 0xFF).sum();
+        (sum / colors.len() as u32) << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
+
+impl Hooks for Coverage {
+    fn before_instruction(&mut self, schip8: &SChip8) -> bool {
+        // Instructions that cannot be decoded fault, their two bytes count as executed all the same
+        let pc = schip8.pc();
+        let size = decode_at(schip8.memory(), pc).map_or(2, |instruction| instruction.size());
+        let size = size.min(schip8.memory().len().saturating_sub(pc));
+        self.mark(pc, size, EXECUTED);
+        true
+    }
+
+    fn memory_access(&mut self, addr: usize, len: usize, access: Access) {
+        let flag = match access {
+            Access::Sprite => SPRITE,
+            Access::Read => READ,
+            Access::Write => WRITTEN,
+        };
+        self.mark(addr, len, flag);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::condition::Condition;
+use crate::hooks::{Access, Hooks};
+use crate::instruction::{decode_at, Instruction};
+use crate::schip8::SChip8;
+use std::collections::BTreeMap;
+
+// What the debugger lets the machine do until it stops it again
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Paused,
+    Continue,
+    Step,
+    StepOut { sp: usize }, // Until the stack is below the given depth, also used to step over calls
+    RunTo { addr: usize },
+}
+
+// Why the debugger paused the program
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    // Paused by Debugger::pause, or just created
+    Pause,
+    // A step or run to an address finished
+    Step,
+    // The program counter reached a breakpoint whose condition held
+    Breakpoint,
+    // The instruction at pc accessed the watched byte at addr, the program stopped right after it
+    Watchpoint {
+        addr: usize,
+        access: Access,
+        pc: usize,
+    },
+}
+
+// Range of memory to stop at when it is read or written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub addr: usize,
+    pub len: usize,
+    pub read: bool,  // Stop at reads, including sprites drawn by DXYN
+    pub write: bool, // Stop at writes
+}
+
+impl Watchpoint {
+    pub fn contains(&self, addr: usize) -> bool {
+        addr >= self.addr && addr - self.addr < self.len
+    }
+
+    fn triggered_by(&self, addr: usize, len: usize, access: Access) -> bool {
+        let wanted = match access {
+            Access::Sprite | Access::Read => self.read,
+            Access::Write => self.write,
+        };
+        // Saturating, as the range may come from a client and reach to the end of the address space
+        wanted && addr < self.addr.saturating_add(self.len) && self.addr < addr.saturating_add(len)
+    }
+}
+
+// Breakpoints, watchpoints and stepping, independent of how the frontend talks to the user.
+// Run the machine with SChip8::run_frame_with(key, &mut debugger) and skip frames while paused.
+pub struct Debugger {
+    breakpoints: BTreeMap<usize, Option<Condition>>,
+    watchpoints: Vec<Watchpoint>,
+    mode: Mode,
+    stop: Stop,
+    // Set until the first instruction after resuming, which breakpoints do not stop
+    resumed: bool,
+    pc: usize,               // Address of the instruction being executed
+    triggered: Option<Stop>, // Watchpoint hit by the instruction being executed
+}
+
+impl Debugger {
+    // Starts paused, so breakpoints can be set before the program runs
+    pub fn new() -> Debugger {
+        Debugger {
+            breakpoints: BTreeMap::new(),
+            watchpoints: Vec::new(),
+            mode: Mode::Paused,
+            stop: Stop::Pause,
+            resumed: false,
+            pc: 0,
+            triggered: None,
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        self.mode == Mode::Paused
+    }
+
+    // Why the program was paused the last time
+    pub fn stop(&self) -> Stop {
+        self.stop
+    }
+
+    pub fn pause(&mut self) {
+        self.pause_with(Stop::Pause);
+    }
+
+    // Stops at the address whenever the condition holds, or always without one.
+    // Returns false if it replaced a breakpoint at the address.
+    pub fn add_breakpoint(&mut self, addr: usize, condition: Option<Condition>) -> bool {
+        self.breakpoints.insert(addr, condition).is_none()
+    }
+
+    // Returns false if there was no breakpoint at the address
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.remove(&addr).is_some()
+    }
+
+    // Addresses of the breakpoints in ascending order, with their conditions
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, Option<&Condition>)> + '_ {
+        self.breakpoints
+            .iter()
+            .map(|(&addr, condition)| (addr, condition.as_ref()))
+    }
+
+    pub fn has_breakpoint(&self, addr: usize) -> bool {
+        self.breakpoints.contains_key(&addr)
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    // Removes the watchpoints starting at the address, returns false if there were none
+    pub fn remove_watchpoint(&mut self, addr: usize) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints
+            .retain(|watchpoint| watchpoint.addr != addr);
+        self.watchpoints.len() != len
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    // Runs until the next breakpoint
+    pub fn resume(&mut self) {
+        self.start(Mode::Continue);
+    }
+
+    // Executes a single instruction
+    pub fn step(&mut self) {
+        self.start(Mode::Step);
+    }
+
+    // Like step, but runs a called subroutine up to its return
+    pub fn step_over(&mut self, schip8: &SChip8) {
+        match decode_at(schip8.memory(), schip8.pc()) {
+            Some(Instruction::Call(_)) => self.start(Mode::StepOut {
+                sp: schip8.sp() + 1,
+            }),
+            _ => self.start(Mode::Step),
+        }
+    }
+
+    // Runs until the current subroutine returned, false if the program is not in one
+    pub fn step_out(&mut self, schip8: &SChip8) -> bool {
+        if schip8.sp() == 0 {
+            return false;
+        }
+        self.start(Mode::StepOut { sp: schip8.sp() });
+        true
+    }
+
+    // Runs until the program counter reaches the address, or a breakpoint comes first
+    pub fn run_to(&mut self, addr: usize) {
+        self.start(Mode::RunTo { addr });
+    }
+
+    fn start(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.resumed = true;
+    }
+
+    fn pause_with(&mut self, stop: Stop) {
+        self.mode = Mode::Paused;
+        self.stop = stop;
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Debugger {
+        Debugger::new()
+    }
+}
+
+impl Hooks for Debugger {
+    fn before_instruction(&mut self, schip8: &SChip8) -> bool {
+        if self.mode == Mode::Paused {
+            return false;
+        }
+        if let Some(stop) = self.triggered.take() {
+            self.pause_with(stop);
+            return false;
+        }
+
+        // The instruction the machine stopped at runs, even if there is a breakpoint on it
+        let resumed = std::mem::take(&mut self.resumed);
+        let pc = schip8.pc();
+        let breakpoint = match self.breakpoints.get(&pc) {
+            Some(Some(condition)) => condition.holds(schip8),
+            Some(None) => true,
+            None => false,
+        };
+        let step_done = match self.mode {
+            Mode::Step => true,
+            Mode::StepOut { sp } => schip8.sp() < sp,
+            Mode::RunTo { addr } => pc == addr,
+            Mode::Paused | Mode::Continue => false,
+        };
+        if !resumed && breakpoint {
+            self.pause_with(Stop::Breakpoint);
+            return false;
+        }
+        if !resumed && step_done {
+            self.pause_with(Stop::Step);
+            return false;
+        }
+        self.pc = pc;
+        true
+    }
+
+    fn memory_access(&mut self, addr: usize, len: usize, access: Access) {
+        if self.triggered.is_some() {
+            return;
+        }
+        let watchpoint = self
+            .watchpoints
+            .iter()
+            .find(|watchpoint| watchpoint.triggered_by(addr, len, access));
+        if let Some(watchpoint) = watchpoint {
+            self.triggered = Some(Stop::Watchpoint {
+                addr: addr.max(watchpoint.addr),
+                access,
+                pc: self.pc,
+            });
+        }
+    }
+}
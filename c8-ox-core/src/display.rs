@@ -0,0 +1,209 @@
+use crate::snapshot::{Reader, SnapshotError, Writer};
+
+pub const CHIP8_SCREEN_WIDTH: usize = 64;
+pub const CHIP8_SCREEN_HEIGHT: usize = 32;
+// Only included for documentation purposes
+//pub const CHIP8_NUM_PIXELS: usize = CHIP8_SCREEN_WIDTH * CHIP8_SCREEN_HEIGHT;
+
+pub const SCHIP8_SCREEN_WIDTH: usize = 128;
+pub const SCHIP8_SCREEN_HEIGHT: usize = 64;
+pub const SCHIP8_NUM_PIXELS: usize = SCHIP8_SCREEN_WIDTH * SCHIP8_SCREEN_HEIGHT;
+
+// Number of bitplanes, XO-CHIP has two while everything before only uses the first one
+pub const NUM_PLANES: usize = 2;
+// Number of colours resulting from the bitplanes
+pub const NUM_COLORS: usize = 1 << NUM_PLANES;
+
+// Framebuffer with one bit per plane and pixel.
+// Every pixel byte holds the bit of plane 0 in bit 0 and the bit of plane 1 in bit 1, so the buffer is
+// at the same time the composed colour-index buffer the frontends draw.
+pub struct Display {
+    pixels: [u8; SCHIP8_NUM_PIXELS], // Row-major, the stride is the current width
+    width: usize,                    //
+    height: usize,                   //
+    extended: bool,                  // SCHIP8 128x64 mode
+    planes: u8,                      // Bit mask of the planes drawn to, set by FN01
+}
+
+impl Display {
+    pub fn new() -> Display {
+        Display {
+            pixels: [0; SCHIP8_NUM_PIXELS],
+            width: CHIP8_SCREEN_WIDTH,
+            height: CHIP8_SCREEN_HEIGHT,
+            extended: false,
+            planes: 0x1,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn extended(&self) -> bool {
+        self.extended
+    }
+
+    pub fn set_extended(&mut self, extended: bool) {
+        self.extended = extended;
+        if extended {
+            self.width = SCHIP8_SCREEN_WIDTH;
+            self.height = SCHIP8_SCREEN_HEIGHT;
+        } else {
+            self.width = CHIP8_SCREEN_WIDTH;
+            self.height = CHIP8_SCREEN_HEIGHT;
+        }
+    }
+
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & ((1 << NUM_PLANES) - 1);
+    }
+
+    // Colour indices (0 to NUM_COLORS - 1) of the visible screen, row by row
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels[..self.width * self.height]
+    }
+
+    // Colour index of a single pixel
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    // Clears the selected planes
+    pub fn clear(&mut self) {
+        let mask = !self.planes;
+        for pixel in self.pixels.iter_mut() {
+            *pixel &= mask;
+        }
+    }
+
+    // Moves the contents of the selected planes by (dx, dy), pixels moved in are blank
+    pub fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width as isize, self.height as isize);
+        let mask = self.planes;
+        let old = self.pixels;
+
+        for y in 0..height {
+            for x in 0..width {
+                let (src_x, src_y) = (x - dx, y - dy);
+                let moved = if src_x >= 0 && src_x < width && src_y >= 0 && src_y < height {
+                    old[(src_y * width + src_x) as usize] & mask
+                } else {
+                    0
+                };
+                let pixel = &mut self.pixels[(y * width + x) as usize];
+                *pixel = (*pixel & !mask) | moved;
+            }
+        }
+    }
+
+    // XORs one sprite row onto a single plane, returns whether a set pixel was unset.
+    // The row is given with its leftmost pixel in the most significant bit.
+    pub(crate) fn draw_row(&mut self, x: usize, y: usize, row: u16, plane: u8, clip: bool) -> bool {
+        let mut collision = false;
+        if y >= self.height && clip {
+            return collision;
+        }
+        let y = y % self.height;
+
+        for col in 0..16 {
+            if row & (0x8000 >> col) == 0 {
+                continue;
+            }
+            let mut xi = x + col;
+            if xi >= self.width {
+                if clip {
+                    break;
+                }
+                xi %= self.width;
+            }
+
+            let pixel = &mut self.pixels[y * self.width + xi];
+            if *pixel & plane != 0 {
+                collision = true;
+            }
+            *pixel ^= plane;
+        }
+
+        collision
+    }
+
+    // The whole buffer is stored, including the part hidden in low resolution mode
+    pub(crate) fn save(&self, writer: &mut Writer) {
+        writer.bool(self.extended);
+        writer.u8(self.planes);
+        writer.bytes(&self.pixels);
+    }
+
+    pub(crate) fn load(reader: &mut Reader) -> Result<Display, SnapshotError> {
+        let mut display = Display::new();
+        display.set_extended(reader.bool()?);
+        let planes = reader.u8()?;
+        if planes >> NUM_PLANES != 0 {
+            return Err(SnapshotError::Invalid("plane selection"));
+        }
+        display.select_planes(planes);
+        let pixels = reader.bytes(SCHIP8_NUM_PIXELS)?;
+        if pixels.iter().any(|&pixel| pixel as usize >= NUM_COLORS) {
+            return Err(SnapshotError::Invalid("pixel"));
+        }
+        display.pixels.copy_from_slice(pixels);
+        Ok(display)
+    }
+}
+
+impl Default for Display {
+    fn default() -> Display {
+        Display::new()
+    }
+}
+
+// The colours the frontends draw the colour indices with, as 0xRRGGBB
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [u32; NUM_COLORS],
+}
+
+impl Palette {
+    // Parses a comma separated list of NUM_COLORS hex colours like "000000,FFFFFF,AAAAAA,555555"
+    pub fn parse(colors: &str) -> Option<Palette> {
+        let mut palette = Palette::default();
+        let colors: Vec<&str> = colors
+            .split(',')
+            .map(|c| c.trim().trim_start_matches('#'))
+            .collect();
+        if colors.len() != NUM_COLORS {
+            return None;
+        }
+        for (i, color) in colors.iter().enumerate() {
+            if color.len() != 6 {
+                return None;
+            }
+            palette.colors[i] = u32::from_str_radix(color, 16).ok()?;
+        }
+        Some(palette)
+    }
+
+    // Colour of an index as (red, green, blue)
+    pub fn rgb(&self, index: u8) -> (u8, u8, u8) {
+        let color = self.colors[index as usize % NUM_COLORS];
+        ((color >> 16) as u8, (color >> 8) as u8, color as u8)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        // Background, plane 0, plane 1, both planes
+        Palette {
+            colors: [0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555],
+        }
+    }
+}
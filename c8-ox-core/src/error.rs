@@ -0,0 +1,53 @@
+use std::{error, fmt};
+
+// What went wrong while executing an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    // The opcode is unknown, or not available on the emulated platform
+    UnknownInstruction,
+    // 0NNN would execute native RCA 1802 machine code, which is not emulated
+    MachineCodeCall,
+    // 00EE without a matching 2NNN
+    StackUnderflow,
+    // 2NNN with the stack of the platform already full
+    StackOverflow,
+    // The program counter left the memory
+    PcOutOfBounds,
+    // An instruction accessed memory behind the end of the memory
+    MemoryOutOfBounds { addr: usize },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::UnknownInstruction => write!(f, "unknown instruction"),
+            Fault::MachineCodeCall => write!(f, "machine code routines are not supported"),
+            Fault::StackUnderflow => write!(f, "stack underflow"),
+            Fault::StackOverflow => write!(f, "stack overflow"),
+            Fault::PcOutOfBounds => write!(f, "program counter out of memory"),
+            Fault::MemoryOutOfBounds { addr } => {
+                write!(f, "memory access out of bounds at {:04X}", addr)
+            }
+        }
+    }
+}
+
+// Error returned by SChip8::run, the machine state is left as it was before the faulting instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExecutionError {
+    pub pc: usize,
+    pub opcode: u16,
+    pub fault: Fault,
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (opcode {:04X} at {:04X})",
+            self.fault, self.opcode, self.pc
+        )
+    }
+}
+
+impl error::Error for ExecutionError {}
@@ -0,0 +1,10 @@
+// Persistence of the RPL user flags written by FX75 and read by FX85.
+// On the HP-48 they survived between program runs and games keep their high scores in them,
+// so frontends can plug in a storage to keep them, e.g. in a file per ROM.
+pub trait FlagStorage {
+    // Flags stored by an earlier run, all zero if there are none
+    fn load(&mut self) -> [u8; 16];
+
+    // Called whenever FX75 changed the flags
+    fn save(&mut self, flags: &[u8; 16]);
+}
@@ -0,0 +1,591 @@
+use crate::instruction::{decode_at, Instruction, Syntax};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::Range;
+
+// How control gets from a basic block to the next one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeKind {
+    Next, // Runs on with the following instruction, also after a call or a skip not taken
+    Jump, // Jumps there with 1NNN
+    Skip, // Skips the following instruction
+}
+
+// Instructions in a row that are only entered at the first and only left after the last
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub instructions: Vec<(usize, Instruction)>,
+    pub successors: Vec<(usize, EdgeKind)>,
+    pub call: Option<usize>, // Subroutine called by the last instruction
+}
+
+// Why the analysis could not follow the program at an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unresolved {
+    ComputedJump,          // BNNN, the target depends on V0
+    MachineCall,           // 0NNN runs machine code of the original computer
+    SelfModifying(usize),  // Writes to the instruction at the address
+    Invalid,               // Not an instruction, or cut off at the end of the program
+    OutsideProgram(usize), // Jumps or calls to an address outside the program
+}
+
+impl fmt::Display for Unresolved {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unresolved::ComputedJump => write!(f, "computed jump"),
+            Unresolved::MachineCall => write!(f, "machine code call"),
+            Unresolved::SelfModifying(addr) => write!(f, "writes to the code at {:03X}", addr),
+            Unresolved::Invalid => write!(f, "not an instruction"),
+            Unresolved::OutsideProgram(addr) => {
+                write!(f, "goes to {:03X} outside the program", addr)
+            }
+        }
+    }
+}
+
+// A subroutine, or the program itself starting at its load address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Function {
+    pub entry: usize,
+    pub blocks: Vec<usize>, // Starts of the blocks reached from the entry without calling
+    pub calls: Vec<usize>,  // Entries of the subroutines called
+}
+
+// Control flow graph of a program, found statically by following jumps, calls and skips from the
+// load address. Computed jumps and jumps to addresses that are not instructions cannot be
+// followed and are listed as unresolved, like writes to the code through an address set in the
+// same block. Writes through an address only known at runtime are not checked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ControlFlow {
+    program: Range<usize>,
+    blocks: BTreeMap<usize, BasicBlock>, // By start
+    functions: Vec<Function>,            // Sorted by entry
+    unresolved: Vec<(usize, Unresolved)>,
+    unreached: Vec<(usize, usize)>, // Inclusive ranges of program bytes no instruction covers
+}
+
+// Where control can go after an instruction, and whether it ends a basic block
+fn successors(
+    instruction: Instruction,
+    next: usize,
+    skipped: usize,
+) -> (Vec<(usize, EdgeKind)>, bool) {
+    use Instruction::*;
+
+    match instruction {
+        Jump(addr) => (vec![(addr as usize, EdgeKind::Jump)], true),
+        Call(_) => (vec![(next, EdgeKind::Next)], true),
+        Return | Exit | JumpOffset(_) => (Vec::new(), true),
+        SkipEqualByte(..) | SkipNotEqualByte(..) | SkipEqual(..) | SkipNotEqual(..)
+        | SkipKey(_) | SkipNotKey(_) => (
+            vec![(next, EdgeKind::Next), (skipped, EdgeKind::Skip)],
+            true,
+        ),
+        _ => (vec![(next, EdgeKind::Next)], false),
+    }
+}
+
+impl ControlFlow {
+    pub fn analyze(program: &[u8], load_address: usize) -> ControlFlow {
+        let mut memory = vec![0; load_address];
+        memory.extend_from_slice(program);
+        let range = load_address..memory.len();
+        let decode = |addr: usize| match decode_at(&memory, addr) {
+            Some(Instruction::Unknown(_)) => None,
+            instruction => instruction.filter(|_| range.contains(&addr)),
+        };
+        // Where a skip lands when the instruction at next is skipped, F000 NNNN is skipped whole
+        let skipped = |next: usize| next + decode(next).map_or(2, |i| i.size());
+
+        // Every instruction reachable from the load address
+        let mut instructions = BTreeMap::new();
+        let mut unresolved = BTreeMap::new();
+        let mut leaders = BTreeSet::new();
+        let mut entries = BTreeSet::new();
+        leaders.insert(load_address);
+        entries.insert(load_address);
+        let mut pending = vec![load_address];
+        while let Some(addr) = pending.pop() {
+            if instructions.contains_key(&addr) {
+                continue;
+            }
+            let instruction = match decode(addr) {
+                Some(instruction) => instruction,
+                None => {
+                    unresolved.insert(addr, Unresolved::Invalid);
+                    continue;
+                }
+            };
+            instructions.insert(addr, instruction);
+
+            let next = addr + instruction.size();
+            let (mut targets, ends_block) = successors(instruction, next, skipped(next));
+            match instruction {
+                Instruction::Call(target) => {
+                    entries.insert(target as usize);
+                    targets.push((target as usize, EdgeKind::Jump));
+                }
+                Instruction::JumpOffset(_) => {
+                    unresolved.insert(addr, Unresolved::ComputedJump);
+                }
+                Instruction::MachineCall(_) => {
+                    unresolved.insert(addr, Unresolved::MachineCall);
+                }
+                _ => {}
+            }
+            for (target, _) in targets {
+                if !range.contains(&target) {
+                    unresolved.insert(addr, Unresolved::OutsideProgram(target));
+                    continue;
+                }
+                if ends_block {
+                    leaders.insert(target);
+                }
+                pending.push(target);
+            }
+        }
+
+        // Blocks run from a leader up to an instruction ending them or the next leader. Writes
+        // through an address loaded into I in the same block are collected on the way.
+        let mut blocks = BTreeMap::new();
+        let mut writes = Vec::new();
+        for &start in leaders
+            .iter()
+            .filter(|addr| instructions.contains_key(addr))
+        {
+            let mut block = BasicBlock {
+                start,
+                instructions: Vec::new(),
+                successors: Vec::new(),
+                call: None,
+            };
+            let mut ar = None; // The address in I, if known
+            let mut addr = start;
+            while let Some(&instruction) = instructions.get(&addr) {
+                use Instruction::*;
+
+                block.instructions.push((addr, instruction));
+                let written = match instruction {
+                    Bcd(_) => 3,
+                    Store(x) => x as usize + 1,
+                    StoreRange(x, y) => (x as i32 - y as i32).unsigned_abs() as usize + 1,
+                    _ => 0,
+                };
+                if let (Some(target), true) = (ar, written > 0) {
+                    writes.push((addr, target, written));
+                }
+                ar = match instruction {
+                    LoadI(target) => Some(target as usize),
+                    LoadLongI(target) => Some(target as usize),
+                    AddI(_) | Font(_) | BigFont(_) | Store(_) | Load(_) => None,
+                    _ => ar,
+                };
+
+                let next = addr + instruction.size();
+                let (targets, ends_block) = successors(instruction, next, skipped(next));
+                if ends_block || leaders.contains(&next) || !instructions.contains_key(&next) {
+                    block.successors = targets
+                        .into_iter()
+                        .filter(|(target, _)| range.contains(target))
+                        .collect();
+                    if let Call(target) = instruction {
+                        block.call = Some(target as usize);
+                    }
+                    break;
+                }
+                addr = next;
+            }
+            blocks.insert(start, block);
+        }
+
+        let code: BTreeSet<usize> = instructions
+            .iter()
+            .flat_map(|(&addr, instruction)| addr..addr + instruction.size())
+            .collect();
+        for (addr, target, len) in writes {
+            if let Some(written) = (target..target + len).find(|byte| code.contains(byte)) {
+                unresolved.insert(addr, Unresolved::SelfModifying(written));
+            }
+        }
+
+        let mut unreached = Vec::new();
+        let mut addr = range.start;
+        while addr < range.end {
+            if code.contains(&addr) {
+                addr += 1;
+                continue;
+            }
+            let start = addr;
+            while addr < range.end && !code.contains(&addr) {
+                addr += 1;
+            }
+            unreached.push((start, addr - 1));
+        }
+
+        let functions = entries
+            .iter()
+            .filter(|entry| blocks.contains_key(entry))
+            .map(|&entry| function(&blocks, &entries, entry))
+            .collect();
+
+        ControlFlow {
+            program: range,
+            blocks,
+            functions,
+            unresolved: unresolved.into_iter().collect(),
+            unreached,
+        }
+    }
+
+    // Blocks sorted by address
+    pub fn blocks(&self) -> impl Iterator<Item = &BasicBlock> + '_ {
+        self.blocks.values()
+    }
+
+    pub fn block_at(&self, addr: usize) -> Option<&BasicBlock> {
+        self.blocks.get(&addr)
+    }
+
+    // The program and its subroutines, sorted by entry
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    // Instructions the analysis could not follow, sorted by address
+    pub fn unresolved(&self) -> &[(usize, Unresolved)] {
+        &self.unresolved
+    }
+
+    // Ranges of the program no reachable instruction covers, like data or dead code
+    pub fn unreached(&self) -> &[(usize, usize)] {
+        &self.unreached
+    }
+
+    // The graph in the language of Graphviz, with a cluster per function and calls as dashed edges
+    pub fn to_dot(&self, name: &str) -> String {
+        let node = |addr: usize| format!("b{:03X}", addr);
+        let mut dot = format!(
+            "digraph \"{}\" {{\n    node [shape=box fontname=monospace];\n",
+            name.replace('"', "'")
+        );
+
+        // Blocks shared by several functions are drawn in the first one
+        let mut drawn = BTreeSet::new();
+        for function in self.functions.iter() {
+            dot += &format!(
+                "    subgraph cluster_{:03X} {{\n        label=\"{}\";\n",
+                function.entry,
+                if function.entry == self.program.start {
+                    "program".to_string()
+                } else {
+                    format!("sub {:03X}", function.entry)
+                }
+            );
+            for start in function.blocks.iter().filter(|start| drawn.insert(**start)) {
+                let block = &self.blocks[start];
+                let mut label = String::new();
+                let mut color = "";
+                for (addr, instruction) in block.instructions.iter() {
+                    label += &format!(
+                        "{:03X}: {}",
+                        addr,
+                        instruction.format(Syntax::Mnemonic, &|_| None)
+                    );
+                    if let Some((_, reason)) = self.unresolved.iter().find(|(a, _)| a == addr) {
+                        label += &format!(" ; {}", reason);
+                        color = " color=red";
+                    }
+                    label += "\\l";
+                }
+                dot += &format!("        {} [label=\"{}\"{}];\n", node(*start), label, color);
+            }
+            dot += "    }\n";
+        }
+        for (addr, reason) in self.unresolved.iter() {
+            if *reason == Unresolved::Invalid {
+                dot += &format!(
+                    "    {} [label=\"{:03X}: {}\" color=red];\n",
+                    node(*addr),
+                    addr,
+                    reason
+                );
+            }
+        }
+
+        for block in self.blocks.values() {
+            for (target, kind) in block.successors.iter() {
+                let style = match kind {
+                    EdgeKind::Next => "",
+                    EdgeKind::Jump => " [label=\"jump\"]",
+                    EdgeKind::Skip => " [label=\"skip\"]",
+                };
+                dot += &format!("    {} -> {}{};\n", node(block.start), node(*target), style);
+            }
+            if let Some(target) = block.call.filter(|target| self.blocks.contains_key(target)) {
+                dot += &format!(
+                    "    {} -> {} [style=dashed label=\"call\"];\n",
+                    node(block.start),
+                    node(target)
+                );
+            }
+        }
+        dot += "}\n";
+        dot
+    }
+
+    // The graph as JSON, with decimal addresses
+    pub fn to_json(&self) -> String {
+        let list = |items: Vec<String>| format!("[{}]", items.join(","));
+        let numbers = |addrs: &[usize]| list(addrs.iter().map(usize::to_string).collect());
+
+        let blocks = self
+            .blocks
+            .values()
+            .map(|block| {
+                let instructions = block
+                    .instructions
+                    .iter()
+                    .map(|(addr, instruction)| {
+                        format!(
+                            "{{\"addr\":{},\"instruction\":\"{}\"}}",
+                            addr,
+                            instruction.format(Syntax::Mnemonic, &|_| None)
+                        )
+                    })
+                    .collect();
+                let successors = block
+                    .successors
+                    .iter()
+                    .map(|(addr, kind)| {
+                        let kind = match kind {
+                            EdgeKind::Next => "next",
+                            EdgeKind::Jump => "jump",
+                            EdgeKind::Skip => "skip",
+                        };
+                        format!("{{\"addr\":{},\"kind\":\"{}\"}}", addr, kind)
+                    })
+                    .collect();
+                format!(
+                    "{{\"start\":{},\"instructions\":{},\"successors\":{},\"call\":{}}}",
+                    block.start,
+                    list(instructions),
+                    list(successors),
+                    block
+                        .call
+                        .map_or("null".to_string(), |addr| addr.to_string())
+                )
+            })
+            .collect();
+        let functions = self
+            .functions
+            .iter()
+            .map(|function| {
+                format!(
+                    "{{\"entry\":{},\"blocks\":{},\"calls\":{}}}",
+                    function.entry,
+                    numbers(&function.blocks),
+                    numbers(&function.calls)
+                )
+            })
+            .collect();
+        let unresolved = self
+            .unresolved
+            .iter()
+            .map(|(addr, reason)| {
+                let (kind, target) = match reason {
+                    Unresolved::ComputedJump => ("computed_jump", None),
+                    Unresolved::MachineCall => ("machine_call", None),
+                    Unresolved::SelfModifying(target) => ("self_modifying", Some(target)),
+                    Unresolved::Invalid => ("invalid", None),
+                    Unresolved::OutsideProgram(target) => ("outside_program", Some(target)),
+                };
+                match target {
+                    Some(target) => format!(
+                        "{{\"addr\":{},\"kind\":\"{}\",\"target\":{}}}",
+                        addr, kind, target
+                    ),
+                    None => format!("{{\"addr\":{},\"kind\":\"{}\"}}", addr, kind),
+                }
+            })
+            .collect();
+        let unreached = self
+            .unreached
+            .iter()
+            .map(|(start, end)| format!("{{\"start\":{},\"end\":{}}}", start, end))
+            .collect();
+        format!(
+            "{{\"entry\":{},\"blocks\":{},\"functions\":{},\"unresolved\":{},\"unreached\":{}}}\n",
+            self.program.start,
+            list(blocks),
+            list(functions),
+            list(unresolved),
+            list(unreached)
+        )
+    }
+}
+
+// The blocks reached from the entry without following calls. Jumps to the entry of another
+// function are taken as tail calls and not followed either.
+fn function(
+    blocks: &BTreeMap<usize, BasicBlock>,
+    entries: &BTreeSet<usize>,
+    entry: usize,
+) -> Function {
+    let mut reached = BTreeSet::new();
+    let mut calls = BTreeSet::new();
+    let mut pending = vec![entry];
+    while let Some(start) = pending.pop() {
+        let block = match blocks.get(&start) {
+            Some(block) if reached.insert(start) => block,
+            _ => continue,
+        };
+        calls.extend(block.call);
+        pending.extend(
+            block
+                .successors
+                .iter()
+                .map(|(target, _)| *target)
+                .filter(|target| *target == entry || !entries.contains(target)),
+        );
+    }
+    Function {
+        entry,
+        blocks: reached.into_iter().collect(),
+        calls: calls.into_iter().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze(program: &[u8]) -> ControlFlow {
+        ControlFlow::analyze(program, 0x200)
+    }
+
+    fn block(flow: &ControlFlow, start: usize) -> &BasicBlock {
+        flow.block_at(start).unwrap()
+    }
+
+    #[test]
+    fn skips_start_blocks_behind_both_targets() {
+        let flow = analyze(&[
+            0x30, 0x05, // 200: SE V0, #05
+            0x60, 0x01, // 202: LD V0, #01
+            0x70, 0x01, // 204: ADD V0, #01
+            0x12, 0x06, // 206: JP #206
+        ]);
+        let starts: Vec<usize> = flow.blocks().map(|block| block.start).collect();
+        assert_eq!(starts, [0x200, 0x202, 0x204, 0x206]);
+        assert_eq!(
+            block(&flow, 0x200).successors,
+            [(0x202, EdgeKind::Next), (0x204, EdgeKind::Skip)]
+        );
+        assert_eq!(block(&flow, 0x202).successors, [(0x204, EdgeKind::Next)]);
+        assert_eq!(block(&flow, 0x204).successors, [(0x206, EdgeKind::Next)]);
+        assert_eq!(block(&flow, 0x206).successors, [(0x206, EdgeKind::Jump)]);
+        assert!(flow.unresolved().is_empty());
+        assert!(flow.unreached().is_empty());
+    }
+
+    #[test]
+    fn skips_long_instructions_whole() {
+        let flow = analyze(&[
+            0x40, 0x00, // 200: SNE V0, #00
+            0xF0, 0x00, 0x12, 0x34, // 202: LD I, LONG #1234
+            0x00, 0xFD, // 206: EXIT
+        ]);
+        assert_eq!(
+            block(&flow, 0x200).successors,
+            [(0x202, EdgeKind::Next), (0x206, EdgeKind::Skip)]
+        );
+        assert_eq!(block(&flow, 0x202).instructions.len(), 1);
+    }
+
+    #[test]
+    fn calls_start_functions() {
+        let flow = analyze(&[
+            0x22, 0x06, // 200: CALL #206
+            0x00, 0xFD, // 202: EXIT
+            0x12, 0x04, // 204: JP #204, never reached
+            0x00, 0xEE, // 206: RET
+        ]);
+        let call = block(&flow, 0x200);
+        assert_eq!(call.call, Some(0x206));
+        assert_eq!(call.successors, [(0x202, EdgeKind::Next)]);
+        assert!(block(&flow, 0x206).successors.is_empty());
+        assert_eq!(
+            flow.functions(),
+            [
+                Function {
+                    entry: 0x200,
+                    blocks: vec![0x200, 0x202],
+                    calls: vec![0x206],
+                },
+                Function {
+                    entry: 0x206,
+                    blocks: vec![0x206],
+                    calls: Vec::new(),
+                },
+            ]
+        );
+        assert_eq!(flow.unreached(), [(0x204, 0x205)]);
+        assert!(flow
+            .to_dot("test")
+            .contains("b200 -> b206 [style=dashed label=\"call\"];"));
+    }
+
+    #[test]
+    fn reports_what_cannot_be_followed() {
+        let flow = analyze(&[
+            0x60, 0x02, // 200: LD V0, #02
+            0x33, 0x00, // 202: SE V3, #00
+            0xB3, 0x00, // 204: JP V0, #300
+            0x13, 0x00, // 206: JP #300, outside the program
+        ]);
+        assert_eq!(
+            flow.unresolved(),
+            [
+                (0x204, Unresolved::ComputedJump),
+                (0x206, Unresolved::OutsideProgram(0x300)),
+            ]
+        );
+        assert!(block(&flow, 0x204).successors.is_empty());
+        assert!(block(&flow, 0x206).successors.is_empty());
+        assert!(flow
+            .to_json()
+            .contains("{\"addr\":516,\"kind\":\"computed_jump\"}"));
+
+        // Cut off in the middle of an instruction
+        let flow = analyze(&[0x60, 0x02, 0x12]);
+        assert_eq!(flow.unresolved(), [(0x202, Unresolved::Invalid)]);
+        assert_eq!(flow.unreached(), [(0x202, 0x202)]);
+    }
+
+    #[test]
+    fn finds_writes_to_the_code() {
+        let flow = analyze(&[
+            0xA2, 0x04, // 200: LD I, #204
+            0xF1, 0x55, // 202: LD [I], V1
+            0x12, 0x00, // 204: JP #200
+        ]);
+        assert_eq!(
+            flow.unresolved(),
+            [(0x202, Unresolved::SelfModifying(0x204))]
+        );
+
+        // Writes to data, or through an address changed on the way, are not reported
+        let flow = analyze(&[
+            0xA2, 0x0C, // 200: LD I, #20C
+            0xF0, 0x55, // 202: LD [I], V0
+            0xA2, 0x00, // 204: LD I, #200
+            0xF0, 0x1E, // 206: ADD I, V0
+            0xF0, 0x33, // 208: LD B, V0
+            0x00, 0xFD, // 20A: EXIT
+            0x00, // 20C: data
+        ]);
+        assert!(flow.unresolved().is_empty());
+        assert_eq!(flow.unreached(), [(0x20C, 0x20C)]);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::schip8::SChip8;
+
+// How an instruction accessed the memory, see Hooks::memory_access
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Sprite, // Sprite data drawn by DXYN
+    Read,   // Data read by FX65, 5XY3 and F002
+    Write,  // Data written by FX33, FX55 and 5XY2
+}
+
+// Points where tools like the debugger can watch and stop the interpreter, see SChip8::run_frame_with.
+// Every method does nothing by default. The hooks are a type parameter, so SChip8::run_frame with
+// NoHooks compiles to the same loop as if they did not exist.
+pub trait Hooks {
+    // Called before the instruction at SChip8::pc is executed.
+    // Returning false stops the frame before it, the next run_frame_with continues with it.
+    fn before_instruction(&mut self, _schip8: &SChip8) -> bool {
+        true
+    }
+
+    // Called while executing an instruction that accesses the len bytes starting at addr,
+    // right before the access. Instructions fetched by the program counter are not reported.
+    fn memory_access(&mut self, _addr: usize, _len: usize, _access: Access) {}
+
+    // Called at the end of every frame, after the timers ticked
+    fn frame_finished(&mut self, _schip8: &SChip8) {}
+}
+
+impl<H: Hooks + ?Sized> Hooks for &mut H {
+    fn before_instruction(&mut self, schip8: &SChip8) -> bool {
+        (**self).before_instruction(schip8)
+    }
+
+    fn memory_access(&mut self, addr: usize, len: usize, access: Access) {
+        (**self).memory_access(addr, len, access)
+    }
+
+    fn frame_finished(&mut self, schip8: &SChip8) {
+        (**self).frame_finished(schip8)
+    }
+}
+
+// Hooks that are not always there, like a tracer enabled by an option
+impl<H: Hooks> Hooks for Option<H> {
+    fn before_instruction(&mut self, schip8: &SChip8) -> bool {
+        self.as_mut()
+            .is_none_or(|hooks| hooks.before_instruction(schip8))
+    }
+
+    fn memory_access(&mut self, addr: usize, len: usize, access: Access) {
+        if let Some(hooks) = self {
+            hooks.memory_access(addr, len, access);
+        }
+    }
+
+    fn frame_finished(&mut self, schip8: &SChip8) {
+        if let Some(hooks) = self {
+            hooks.frame_finished(schip8);
+        }
+    }
+}
+
+// Two hooks at once, like the debugger and a tracer. The second one only sees the instructions the
+// first one let run, so a tracer after the debugger does not log instructions twice.
+impl<A: Hooks, B: Hooks> Hooks for (A, B) {
+    fn before_instruction(&mut self, schip8: &SChip8) -> bool {
+        self.0.before_instruction(schip8) && self.1.before_instruction(schip8)
+    }
+
+    fn memory_access(&mut self, addr: usize, len: usize, access: Access) {
+        self.0.memory_access(addr, len, access);
+        self.1.memory_access(addr, len, access);
+    }
+
+    fn frame_finished(&mut self, schip8: &SChip8) {
+        self.0.frame_finished(schip8);
+        self.1.frame_finished(schip8);
+    }
+}
+
+// Three hooks at once, each one only seeing the instructions the ones before it let run
+impl<A: Hooks, B: Hooks, C: Hooks> Hooks for (A, B, C) {
+    fn before_instruction(&mut self, schip8: &SChip8) -> bool {
+        self.0.before_instruction(schip8)
+            && self.1.before_instruction(schip8)
+            && self.2.before_instruction(schip8)
+    }
+
+    fn memory_access(&mut self, addr: usize, len: usize, access: Access) {
+        self.0.memory_access(addr, len, access);
+        self.1.memory_access(addr, len, access);
+        self.2.memory_access(addr, len, access);
+    }
+
+    fn frame_finished(&mut self, schip8: &SChip8) {
+        self.0.frame_finished(schip8);
+        self.1.frame_finished(schip8);
+        self.2.frame_finished(schip8);
+    }
+}
+
+// Hooks that never look at or stop anything, used by SChip8::run_frame
+pub struct NoHooks;
+
+impl Hooks for NoHooks {}
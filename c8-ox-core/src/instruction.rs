@@ -0,0 +1,385 @@
+use crate::platform::InstructionSet;
+use std::fmt;
+
+// A decoded opcode. X and Y are register indices, N, NN and NNN the operands in the order of the opcode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    ScrollDown(u8),           // 00CN
+    ScrollUp(u8),             // 00DN
+    Clear,                    // 00E0
+    Return,                   // 00EE
+    ScrollRight,              // 00FB
+    ScrollLeft,               // 00FC
+    Exit,                     // 00FD
+    LowRes,                   // 00FE
+    HighRes,                  // 00FF
+    MachineCall(u16),         // 0NNN
+    Jump(u16),                // 1NNN
+    Call(u16),                // 2NNN
+    SkipEqualByte(u8, u8),    // 3XNN
+    SkipNotEqualByte(u8, u8), // 4XNN
+    SkipEqual(u8, u8),        // 5XY0
+    StoreRange(u8, u8),       // 5XY2
+    LoadRange(u8, u8),        // 5XY3
+    LoadByte(u8, u8),         // 6XNN
+    AddByte(u8, u8),          // 7XNN
+    Move(u8, u8),             // 8XY0
+    Or(u8, u8),               // 8XY1
+    And(u8, u8),              // 8XY2
+    Xor(u8, u8),              // 8XY3
+    Add(u8, u8),              // 8XY4
+    Sub(u8, u8),              // 8XY5
+    ShiftRight(u8, u8),       // 8XY6
+    SubReverse(u8, u8),       // 8XY7
+    ShiftLeft(u8, u8),        // 8XYE
+    SkipNotEqual(u8, u8),     // 9XY0
+    LoadI(u16),               // ANNN
+    JumpOffset(u16),          // BNNN
+    Random(u8, u8),           // CXNN
+    Draw(u8, u8, u8),         // DXYN
+    SkipKey(u8),              // EX9E
+    SkipNotKey(u8),           // EXA1
+    LoadLongI(u16),           // F000 NNNN
+    SelectPlanes(u8),         // FN01
+    LoadAudio,                // F002
+    GetDelay(u8),             // FX07
+    WaitKey(u8),              // FX0A
+    SetDelay(u8),             // FX15
+    SetSound(u8),             // FX18
+    AddI(u8),                 // FX1E
+    Font(u8),                 // FX29
+    BigFont(u8),              // FX30
+    Bcd(u8),                  // FX33
+    SetPitch(u8),             // FX3A
+    Store(u8),                // FX55
+    Load(u8),                 // FX65
+    StoreFlags(u8),           // FX75
+    LoadFlags(u8),            // FX85
+    Unknown(u16),             // Anything else
+}
+
+// Notation used when formatting instructions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    // The classic mnemonics of Cowgod's reference, extended for SCHIP and XO-CHIP
+    Mnemonic,
+    // The language of the Octo assembler
+    Octo,
+}
+
+// Decodes an opcode. F000 is the only instruction longer than an opcode, its address is left 0,
+// use decode_at to read it from the following word.
+pub fn decode(opcode: u16) -> Instruction {
+    use Instruction::*;
+
+    let nibbles = [
+        (opcode >> 12) as u8,
+        (opcode >> 8) as u8 & 0xF,
+        (opcode >> 4) as u8 & 0xF,
+        opcode as u8 & 0xF,
+    ];
+    let nnn = opcode & 0xFFF;
+    let nn = opcode as u8;
+    match nibbles {
+        [0x0, 0x0, 0xC, n] => ScrollDown(n),
+        [0x0, 0x0, 0xD, n] => ScrollUp(n),
+        [0x0, 0x0, 0xE, 0x0] => Clear,
+        [0x0, 0x0, 0xE, 0xE] => Return,
+        [0x0, 0x0, 0xF, 0xB] => ScrollRight,
+        [0x0, 0x0, 0xF, 0xC] => ScrollLeft,
+        [0x0, 0x0, 0xF, 0xD] => Exit,
+        [0x0, 0x0, 0xF, 0xE] => LowRes,
+        [0x0, 0x0, 0xF, 0xF] => HighRes,
+        [0x0, _, _, _] => MachineCall(nnn),
+        [0x1, _, _, _] => Jump(nnn),
+        [0x2, _, _, _] => Call(nnn),
+        [0x3, x, _, _] => SkipEqualByte(x, nn),
+        [0x4, x, _, _] => SkipNotEqualByte(x, nn),
+        [0x5, x, y, 0x0] => SkipEqual(x, y),
+        [0x5, x, y, 0x2] => StoreRange(x, y),
+        [0x5, x, y, 0x3] => LoadRange(x, y),
+        [0x6, x, _, _] => LoadByte(x, nn),
+        [0x7, x, _, _] => AddByte(x, nn),
+        [0x8, x, y, 0x0] => Move(x, y),
+        [0x8, x, y, 0x1] => Or(x, y),
+        [0x8, x, y, 0x2] => And(x, y),
+        [0x8, x, y, 0x3] => Xor(x, y),
+        [0x8, x, y, 0x4] => Add(x, y),
+        [0x8, x, y, 0x5] => Sub(x, y),
+        [0x8, x, y, 0x6] => ShiftRight(x, y),
+        [0x8, x, y, 0x7] => SubReverse(x, y),
+        [0x8, x, y, 0xE] => ShiftLeft(x, y),
+        [0x9, x, y, 0x0] => SkipNotEqual(x, y),
+        [0xA, _, _, _] => LoadI(nnn),
+        [0xB, _, _, _] => JumpOffset(nnn),
+        [0xC, x, _, _] => Random(x, nn),
+        [0xD, x, y, n] => Draw(x, y, n),
+        [0xE, x, 0x9, 0xE] => SkipKey(x),
+        [0xE, x, 0xA, 0x1] => SkipNotKey(x),
+        [0xF, 0x0, 0x0, 0x0] => LoadLongI(0),
+        [0xF, n, 0x0, 0x1] => SelectPlanes(n),
+        [0xF, 0x0, 0x0, 0x2] => LoadAudio,
+        [0xF, x, 0x0, 0x7] => GetDelay(x),
+        [0xF, x, 0x0, 0xA] => WaitKey(x),
+        [0xF, x, 0x1, 0x5] => SetDelay(x),
+        [0xF, x, 0x1, 0x8] => SetSound(x),
+        [0xF, x, 0x1, 0xE] => AddI(x),
+        [0xF, x, 0x2, 0x9] => Font(x),
+        [0xF, x, 0x3, 0x0] => BigFont(x),
+        [0xF, x, 0x3, 0x3] => Bcd(x),
+        [0xF, x, 0x3, 0xA] => SetPitch(x),
+        [0xF, x, 0x5, 0x5] => Store(x),
+        [0xF, x, 0x6, 0x5] => Load(x),
+        [0xF, x, 0x7, 0x5] => StoreFlags(x),
+        [0xF, x, 0x8, 0x5] => LoadFlags(x),
+        _ => Unknown(opcode),
+    }
+}
+
+// Decodes the instruction at addr, including the address following F000.
+// None if the instruction does not fit into the memory.
+pub fn decode_at(memory: &[u8], addr: usize) -> Option<Instruction> {
+    let word = |addr: usize| {
+        let bytes = memory.get(addr..addr + 2)?;
+        Some(((bytes[0] as u16) << 8) | bytes[1] as u16)
+    };
+    match decode(word(addr)?) {
+        Instruction::LoadLongI(_) => Some(Instruction::LoadLongI(word(addr + 2)?)),
+        instruction => Some(instruction),
+    }
+}
+
+impl Instruction {
+    // Length in bytes
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::LoadLongI(_) => 4,
+            _ => 2,
+        }
+    }
+
+    // Encodes the instruction back into its opcodes, the inverse of decode_at
+    pub fn encode(&self) -> Vec<u16> {
+        use Instruction::*;
+
+        let xy = |op: u16, x: u8, y: u8, n: u16| op << 12 | (x as u16) << 8 | (y as u16) << 4 | n;
+        let xnn = |op: u16, x: u8, nn: u8| op << 12 | (x as u16) << 8 | nn as u16;
+        let opcode = match *self {
+            ScrollDown(n) => 0x00C0 | n as u16,
+            ScrollUp(n) => 0x00D0 | n as u16,
+            Clear => 0x00E0,
+            Return => 0x00EE,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            LowRes => 0x00FE,
+            HighRes => 0x00FF,
+            MachineCall(nnn) => nnn & 0xFFF,
+            Jump(nnn) => 0x1000 | (nnn & 0xFFF),
+            Call(nnn) => 0x2000 | (nnn & 0xFFF),
+            SkipEqualByte(x, nn) => xnn(0x3, x, nn),
+            SkipNotEqualByte(x, nn) => xnn(0x4, x, nn),
+            SkipEqual(x, y) => xy(0x5, x, y, 0x0),
+            StoreRange(x, y) => xy(0x5, x, y, 0x2),
+            LoadRange(x, y) => xy(0x5, x, y, 0x3),
+            LoadByte(x, nn) => xnn(0x6, x, nn),
+            AddByte(x, nn) => xnn(0x7, x, nn),
+            Move(x, y) => xy(0x8, x, y, 0x0),
+            Or(x, y) => xy(0x8, x, y, 0x1),
+            And(x, y) => xy(0x8, x, y, 0x2),
+            Xor(x, y) => xy(0x8, x, y, 0x3),
+            Add(x, y) => xy(0x8, x, y, 0x4),
+            Sub(x, y) => xy(0x8, x, y, 0x5),
+            ShiftRight(x, y) => xy(0x8, x, y, 0x6),
+            SubReverse(x, y) => xy(0x8, x, y, 0x7),
+            ShiftLeft(x, y) => xy(0x8, x, y, 0xE),
+            SkipNotEqual(x, y) => xy(0x9, x, y, 0x0),
+            LoadI(nnn) => 0xA000 | (nnn & 0xFFF),
+            JumpOffset(nnn) => 0xB000 | (nnn & 0xFFF),
+            Random(x, nn) => xnn(0xC, x, nn),
+            Draw(x, y, n) => xy(0xD, x, y, n as u16),
+            SkipKey(x) => xnn(0xE, x, 0x9E),
+            SkipNotKey(x) => xnn(0xE, x, 0xA1),
+            LoadLongI(nnnn) => return vec![0xF000, nnnn],
+            SelectPlanes(n) => xnn(0xF, n, 0x01),
+            LoadAudio => 0xF002,
+            GetDelay(x) => xnn(0xF, x, 0x07),
+            WaitKey(x) => xnn(0xF, x, 0x0A),
+            SetDelay(x) => xnn(0xF, x, 0x15),
+            SetSound(x) => xnn(0xF, x, 0x18),
+            AddI(x) => xnn(0xF, x, 0x1E),
+            Font(x) => xnn(0xF, x, 0x29),
+            BigFont(x) => xnn(0xF, x, 0x30),
+            Bcd(x) => xnn(0xF, x, 0x33),
+            SetPitch(x) => xnn(0xF, x, 0x3A),
+            Store(x) => xnn(0xF, x, 0x55),
+            Load(x) => xnn(0xF, x, 0x65),
+            StoreFlags(x) => xnn(0xF, x, 0x75),
+            LoadFlags(x) => xnn(0xF, x, 0x85),
+            Unknown(opcode) => opcode,
+        };
+        vec![opcode]
+    }
+
+    // The oldest instruction set containing the instruction
+    pub fn instruction_set(&self) -> InstructionSet {
+        use Instruction::*;
+
+        match self {
+            ScrollDown(_) | ScrollRight | ScrollLeft => InstructionSet::SChip11,
+            Exit | LowRes | HighRes | BigFont(_) | StoreFlags(_) | LoadFlags(_) => {
+                InstructionSet::SChip10
+            }
+            ScrollUp(_) | StoreRange(..) | LoadRange(..) | LoadLongI(_) | SelectPlanes(_)
+            | LoadAudio | SetPitch(_) => InstructionSet::XoChip,
+            _ => InstructionSet::Chip8,
+        }
+    }
+
+    // Address jumped to or called, for labels in disassemblies
+    pub fn target(&self) -> Option<u16> {
+        match *self {
+            Instruction::Jump(addr) | Instruction::Call(addr) => Some(addr),
+            _ => None,
+        }
+    }
+
+    // Formats the instruction in the given syntax, with addresses replaced by the names label returns
+    pub fn format(&self, syntax: Syntax, label: &dyn Fn(u16) -> Option<String>) -> String {
+        match syntax {
+            Syntax::Mnemonic => {
+                self.format_mnemonic(|addr| label(addr).unwrap_or_else(|| format!("#{:03X}", addr)))
+            }
+            Syntax::Octo => {
+                self.format_octo(|addr| label(addr).unwrap_or_else(|| format!("0x{:03X}", addr)))
+            }
+        }
+    }
+
+    fn format_mnemonic<T: Fn(u16) -> String>(&self, target: T) -> String {
+        use Instruction::*;
+
+        match *self {
+            ScrollDown(n) => format!("SCD {}", n),
+            ScrollUp(n) => format!("SCU {}", n),
+            Clear => "CLS".to_string(),
+            Return => "RET".to_string(),
+            ScrollRight => "SCR".to_string(),
+            ScrollLeft => "SCL".to_string(),
+            Exit => "EXIT".to_string(),
+            LowRes => "LOW".to_string(),
+            HighRes => "HIGH".to_string(),
+            MachineCall(nnn) => format!("SYS #{:03X}", nnn),
+            Jump(nnn) => format!("JP {}", target(nnn)),
+            Call(nnn) => format!("CALL {}", target(nnn)),
+            SkipEqualByte(x, nn) => format!("SE V{:X}, #{:02X}", x, nn),
+            SkipNotEqualByte(x, nn) => format!("SNE V{:X}, #{:02X}", x, nn),
+            SkipEqual(x, y) => format!("SE V{:X}, V{:X}", x, y),
+            StoreRange(x, y) => format!("LD [I], V{:X}-V{:X}", x, y),
+            LoadRange(x, y) => format!("LD V{:X}-V{:X}, [I]", x, y),
+            LoadByte(x, nn) => format!("LD V{:X}, #{:02X}", x, nn),
+            AddByte(x, nn) => format!("ADD V{:X}, #{:02X}", x, nn),
+            Move(x, y) => format!("LD V{:X}, V{:X}", x, y),
+            Or(x, y) => format!("OR V{:X}, V{:X}", x, y),
+            And(x, y) => format!("AND V{:X}, V{:X}", x, y),
+            Xor(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+            Add(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+            Sub(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+            ShiftRight(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+            SubReverse(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+            ShiftLeft(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+            SkipNotEqual(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+            LoadI(nnn) => format!("LD I, {}", target(nnn)),
+            JumpOffset(nnn) => format!("JP V0, {}", target(nnn)),
+            Random(x, nn) => format!("RND V{:X}, #{:02X}", x, nn),
+            Draw(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+            SkipKey(x) => format!("SKP V{:X}", x),
+            SkipNotKey(x) => format!("SKNP V{:X}", x),
+            LoadLongI(nnnn) => format!("LD I, LONG {}", target(nnnn)),
+            SelectPlanes(n) => format!("PLANE {}", n),
+            LoadAudio => "AUDIO".to_string(),
+            GetDelay(x) => format!("LD V{:X}, DT", x),
+            WaitKey(x) => format!("LD V{:X}, K", x),
+            SetDelay(x) => format!("LD DT, V{:X}", x),
+            SetSound(x) => format!("LD ST, V{:X}", x),
+            AddI(x) => format!("ADD I, V{:X}", x),
+            Font(x) => format!("LD F, V{:X}", x),
+            BigFont(x) => format!("LD HF, V{:X}", x),
+            Bcd(x) => format!("LD B, V{:X}", x),
+            SetPitch(x) => format!("PITCH V{:X}", x),
+            Store(x) => format!("LD [I], V{:X}", x),
+            Load(x) => format!("LD V{:X}, [I]", x),
+            StoreFlags(x) => format!("LD R, V{:X}", x),
+            LoadFlags(x) => format!("LD V{:X}, R", x),
+            Unknown(opcode) => format!("DW #{:04X}", opcode),
+        }
+    }
+
+    // Octo has no skip instructions, they are written as the condition under which the next
+    // instruction is executed
+    fn format_octo<T: Fn(u16) -> String>(&self, target: T) -> String {
+        use Instruction::*;
+
+        match *self {
+            ScrollDown(n) => format!("scroll-down {}", n),
+            ScrollUp(n) => format!("scroll-up {}", n),
+            Clear => "clear".to_string(),
+            Return => "return".to_string(),
+            ScrollRight => "scroll-right".to_string(),
+            ScrollLeft => "scroll-left".to_string(),
+            Exit => "exit".to_string(),
+            LowRes => "lores".to_string(),
+            HighRes => "hires".to_string(),
+            MachineCall(nnn) => format!("0x{:02X} 0x{:02X}", nnn >> 8, nnn & 0xFF),
+            Jump(nnn) => format!("jump {}", target(nnn)),
+            Call(nnn) => format!(":call {}", target(nnn)),
+            SkipEqualByte(x, nn) => format!("if v{:x} != 0x{:02X} then", x, nn),
+            SkipNotEqualByte(x, nn) => format!("if v{:x} == 0x{:02X} then", x, nn),
+            SkipEqual(x, y) => format!("if v{:x} != v{:x} then", x, y),
+            StoreRange(x, y) => format!("save v{:x} - v{:x}", x, y),
+            LoadRange(x, y) => format!("load v{:x} - v{:x}", x, y),
+            LoadByte(x, nn) => format!("v{:x} := 0x{:02X}", x, nn),
+            AddByte(x, nn) => format!("v{:x} += 0x{:02X}", x, nn),
+            Move(x, y) => format!("v{:x} := v{:x}", x, y),
+            Or(x, y) => format!("v{:x} |= v{:x}", x, y),
+            And(x, y) => format!("v{:x} &= v{:x}", x, y),
+            Xor(x, y) => format!("v{:x} ^= v{:x}", x, y),
+            Add(x, y) => format!("v{:x} += v{:x}", x, y),
+            Sub(x, y) => format!("v{:x} -= v{:x}", x, y),
+            ShiftRight(x, y) => format!("v{:x} >>= v{:x}", x, y),
+            SubReverse(x, y) => format!("v{:x} =- v{:x}", x, y),
+            ShiftLeft(x, y) => format!("v{:x} <<= v{:x}", x, y),
+            SkipNotEqual(x, y) => format!("if v{:x} == v{:x} then", x, y),
+            LoadI(nnn) => format!("i := {}", target(nnn)),
+            JumpOffset(nnn) => format!("jump0 {}", target(nnn)),
+            Random(x, nn) => format!("v{:x} := random 0x{:02X}", x, nn),
+            Draw(x, y, n) => format!("sprite v{:x} v{:x} {}", x, y, n),
+            SkipKey(x) => format!("if v{:x} -key then", x),
+            SkipNotKey(x) => format!("if v{:x} key then", x),
+            LoadLongI(nnnn) => format!("i := long {}", target(nnnn)),
+            SelectPlanes(n) => format!("plane {}", n),
+            LoadAudio => "audio".to_string(),
+            GetDelay(x) => format!("v{:x} := delay", x),
+            WaitKey(x) => format!("v{:x} := key", x),
+            SetDelay(x) => format!("delay := v{:x}", x),
+            SetSound(x) => format!("buzzer := v{:x}", x),
+            AddI(x) => format!("i += v{:x}", x),
+            Font(x) => format!("i := hex v{:x}", x),
+            BigFont(x) => format!("i := bighex v{:x}", x),
+            Bcd(x) => format!("bcd v{:x}", x),
+            SetPitch(x) => format!("pitch := v{:x}", x),
+            Store(x) => format!("save v{:x}", x),
+            Load(x) => format!("load v{:x}", x),
+            StoreFlags(x) => format!("saveflags v{:x}", x),
+            LoadFlags(x) => format!("loadflags v{:x}", x),
+            Unknown(opcode) => format!("0x{:02X} 0x{:02X}", opcode >> 8, opcode & 0xFF),
+        }
+    }
+}
+
+// Conventional mnemonics, see Instruction::format for Octo syntax and labels
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.format(Syntax::Mnemonic, &|_| None))
+    }
+}
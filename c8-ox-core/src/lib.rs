@@ -0,0 +1,45 @@
+mod audio;
+mod condition;
+mod coverage;
+mod debugger;
+mod display;
+mod error;
+mod flags;
+mod flow;
+mod font;
+mod hooks;
+mod instruction;
+mod movie;
+mod platform;
+mod profiler;
+mod quirks;
+mod random;
+mod rewind;
+mod rom;
+mod schip8;
+mod snapshot;
+mod symbols;
+mod trace;
+
+pub use audio::*;
+pub use condition::Condition;
+pub use coverage::{ByteUse, Coverage, CoverageError, MEMORY_MAP_WIDTH};
+pub use debugger::{Debugger, Stop, Watchpoint};
+pub use display::*;
+pub use error::{ExecutionError, Fault};
+pub use flags::FlagStorage;
+pub use flow::{BasicBlock, ControlFlow, EdgeKind, Function, Unresolved};
+pub use font::*;
+pub use hooks::{Access, Hooks, NoHooks};
+pub use instruction::{decode, decode_at, Instruction, Syntax};
+pub use movie::{Movie, MovieError, MovieFrame, MOVIE_VERSION};
+pub use platform::{InstructionSet, Platform};
+pub use profiler::Profiler;
+pub use quirks::Quirks;
+pub use random::{Random, SeededRandom};
+pub use rewind::Rewind;
+pub use rom::{Rom, RomError};
+pub use schip8::*;
+pub use snapshot::{SnapshotError, SNAPSHOT_VERSION};
+pub use symbols::{SourceLine, SymbolError, SymbolTable};
+pub use trace::Tracer;
@@ -0,0 +1,278 @@
+use crate::error::ExecutionError;
+use crate::hooks::{Hooks, NoHooks};
+use crate::platform::Platform;
+use crate::random::SeededRandom;
+use crate::rom::{Rom, RomError};
+use crate::schip8::{Frame, SChip8};
+use crate::snapshot::{Reader, SnapshotError, Writer};
+use std::{error, fmt};
+
+// Version of the movie format written by Movie::to_bytes
+pub const MOVIE_VERSION: u16 = 1;
+
+// Every movie starts with these bytes, followed by the version
+const MAGIC: &[u8; 4] = b"C8MV";
+
+// Why a movie could not be read or played back
+#[derive(Debug)]
+pub enum MovieError {
+    // The data does not start with the movie magic
+    NotAMovie,
+    // The movie was written by an incompatible version of the emulator
+    UnsupportedVersion { version: u16 },
+    // The data ends in the middle of the movie
+    Truncated,
+    // A value in the movie is out of range
+    Invalid(&'static str),
+    // The movie was recorded with a different ROM, given as SHA-1 in hex
+    WrongRom { expected: String },
+    // The ROM does not fit into the platform of the movie
+    Rom(RomError),
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::NotAMovie => write!(f, "not a movie"),
+            MovieError::UnsupportedVersion { version } => write!(
+                f,
+                "movie version {} is not supported, expected version {}",
+                version, MOVIE_VERSION
+            ),
+            MovieError::Truncated => write!(f, "movie is truncated"),
+            MovieError::Invalid(what) => write!(f, "movie contains an invalid {}", what),
+            MovieError::WrongRom { expected } => {
+                write!(f, "movie was recorded with the ROM with SHA-1 {}", expected)
+            }
+            MovieError::Rom(error) => error.fmt(f),
+        }
+    }
+}
+
+impl error::Error for MovieError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            MovieError::Rom(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<SnapshotError> for MovieError {
+    fn from(error: SnapshotError) -> MovieError {
+        match error {
+            SnapshotError::NotASnapshot => MovieError::NotAMovie,
+            SnapshotError::UnsupportedVersion { version } => {
+                MovieError::UnsupportedVersion { version }
+            }
+            SnapshotError::Truncated => MovieError::Truncated,
+            SnapshotError::Invalid(what) => MovieError::Invalid(what),
+        }
+    }
+}
+
+impl From<RomError> for MovieError {
+    fn from(error: RomError) -> MovieError {
+        MovieError::Rom(error)
+    }
+}
+
+// Input of one 60 Hz frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MovieFrame {
+    pub key_pad: u16, // Keys held, see SChip8::key_pad
+    pub key: u8,      // Key pressed during the frame, 16 if none was
+}
+
+impl MovieFrame {
+    // Feeds the input to the machine and runs the frame
+    pub fn run(&self, schip8: &mut SChip8) -> Result<Frame, ExecutionError> {
+        self.run_with(schip8, &mut NoHooks)
+    }
+
+    // Like run, calling the hooks on the way, see SChip8::run_frame_with
+    pub fn run_with<H: Hooks>(
+        &self,
+        schip8: &mut SChip8,
+        hooks: &mut H,
+    ) -> Result<Frame, ExecutionError> {
+        schip8.set_key_pad(self.key_pad);
+        schip8.run_frame_with(self.key as usize, hooks)
+    }
+}
+
+// Recorded input of a session, together with everything else needed to reproduce it bit for bit:
+// the ROM, the platform settings and the seed of the random numbers
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_sha1: [u8; 20],
+    pub platform: Platform,
+    pub seed: u64,
+    pub frames: Vec<MovieFrame>,
+    pub final_hash: Option<[u8; 20]>, // SChip8::state_hash after the last frame
+}
+
+impl Movie {
+    // Starts an empty recording, the machine has to be created with the same ROM, platform and seed
+    pub fn new(rom: &Rom, platform: &Platform, seed: u64) -> Movie {
+        Movie {
+            rom_sha1: *rom.sha1(),
+            platform: platform.clone(),
+            seed,
+            frames: Vec::new(),
+            final_hash: None,
+        }
+    }
+
+    // Records the input of the frame about to be run with SChip8::run_frame(key)
+    pub fn record(&mut self, schip8: &SChip8, key: usize) {
+        self.frames.push(MovieFrame {
+            key_pad: schip8.key_pad(),
+            key: key.min(0x10) as u8,
+        });
+    }
+
+    // Creates the machine the movie was recorded on, fails if the ROM differs
+    pub fn machine(&self, rom: &Rom) -> Result<SChip8, MovieError> {
+        if *rom.sha1() != self.rom_sha1 {
+            return Err(MovieError::WrongRom {
+                expected: self.rom_sha1.iter().map(|b| format!("{:02x}", b)).collect(),
+            });
+        }
+        let random = Box::new(SeededRandom::new(self.seed));
+        Ok(SChip8::new(rom, &self.platform, random)?)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(MAGIC, MOVIE_VERSION);
+        writer.bytes(&self.rom_sha1);
+        self.platform.save(&mut writer);
+        writer.u64(self.seed);
+        writer.u32(self.frames.len() as u32);
+        for frame in self.frames.iter() {
+            writer.u16(frame.key_pad);
+            writer.u8(frame.key);
+        }
+        writer.bool(self.final_hash.is_some());
+        if let Some(hash) = self.final_hash {
+            writer.bytes(&hash);
+        }
+        writer.finish()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, MovieError> {
+        let mut reader = Reader::new(data, MAGIC, MOVIE_VERSION)?;
+        let mut rom_sha1 = [0; 20];
+        rom_sha1.copy_from_slice(reader.bytes(20)?);
+        let platform = Platform::load(&mut reader)?;
+        let seed = reader.u64()?;
+        let num_frames = reader.u32()?;
+        let mut frames = Vec::new();
+        for _ in 0..num_frames {
+            let key_pad = reader.u16()?;
+            let key = reader.u8()?;
+            if key > 0x10 {
+                return Err(MovieError::Invalid("key"));
+            }
+            frames.push(MovieFrame { key_pad, key });
+        }
+        let final_hash = if reader.bool()? {
+            let mut hash = [0; 20];
+            hash.copy_from_slice(reader.bytes(20)?);
+            Some(hash)
+        } else {
+            None
+        };
+        reader.finish()?;
+
+        Ok(Movie {
+            rom_sha1,
+            platform,
+            seed,
+            frames,
+            final_hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Draws random digits, waiting for a key before each one
+    const PROGRAM: [u8; 12] = [
+        0xC0, 0x0F, // 200: RND V0, #0F
+        0xF1, 0x0A, // 202: LD V1, K
+        0x80, 0x14, // 204: ADD V0, V1
+        0xF0, 0x29, // 206: LD F, V0
+        0xD0, 0x05, // 208: DRW V0, V0, 5
+        0x12, 0x00, // 20A: JP #200
+    ];
+
+    // Records a session with keys held and pressed every few frames
+    fn record(rom: &Rom, platform: &Platform) -> (Movie, [u8; 20]) {
+        let mut movie = Movie::new(rom, platform, 1234);
+        let mut schip8 = movie.machine(rom).unwrap();
+        for frame in 0..300 {
+            schip8.set_key(frame / 5 % 16, frame % 3 == 0);
+            let key = if frame % 7 == 0 { frame / 7 % 16 } else { 0x10 };
+            movie.record(&schip8, key);
+            schip8.run_frame(key).unwrap();
+        }
+        movie.final_hash = Some(schip8.state_hash());
+        (movie, schip8.state_hash())
+    }
+
+    #[test]
+    fn plays_back_bit_for_bit() {
+        let rom = Rom::new(PROGRAM.to_vec()).unwrap();
+        let platform = Platform::default();
+        let (movie, hash) = record(&rom, &platform);
+        let initial = movie.machine(&rom).unwrap().state_hash();
+        assert_ne!(hash, initial);
+
+        let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+        assert_eq!(movie.frames.len(), 300);
+        assert_eq!(movie.final_hash, Some(hash));
+        let mut schip8 = movie.machine(&rom).unwrap();
+        for frame in movie.frames.iter() {
+            frame.run(&mut schip8).unwrap();
+        }
+        assert_eq!(schip8.state_hash(), hash);
+
+        // A different seed draws different digits
+        let mut reseeded = Movie { seed: 1, ..movie };
+        reseeded.final_hash = None;
+        let mut schip8 = reseeded.machine(&rom).unwrap();
+        for frame in reseeded.frames.iter() {
+            frame.run(&mut schip8).unwrap();
+        }
+        assert_ne!(schip8.state_hash(), hash);
+    }
+
+    #[test]
+    fn rejects_another_rom() {
+        let rom = Rom::new(PROGRAM.to_vec()).unwrap();
+        let (movie, _) = record(&rom, &Platform::default());
+        let mut other = PROGRAM.to_vec();
+        other[1] = 0xFF;
+        match movie.machine(&Rom::new(other).unwrap()) {
+            Err(MovieError::WrongRom { expected }) => assert_eq!(expected, rom.sha1_hex()),
+            _ => panic!("the movie was accepted for another ROM"),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        let rom = Rom::new(PROGRAM.to_vec()).unwrap();
+        let bytes = record(&rom, &Platform::default()).0.to_bytes();
+        assert!(matches!(
+            Movie::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(MovieError::Truncated)
+        ));
+        assert!(matches!(
+            Movie::from_bytes(b"C8SS\x00\x01"),
+            Err(MovieError::NotAMovie)
+        ));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::font::{CHIP8_FONT, SCHIP8_FONT, VIP_FONT};
+use crate::quirks::Quirks;
+use crate::snapshot::{Reader, SnapshotError, Writer};
+
+// Deepest stack a snapshot may bring, far above the 16 levels of any real platform
+const MAX_STACK_DEPTH: usize = 256;
+
+// Opcodes understood by a platform, every set includes the ones before it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstructionSet {
+    Chip8,   // The original 35 instructions
+    SChip10, // + 00FD, 00FE, 00FF, DXY0, FX30, FX75, FX85
+    SChip11, // + 00CN, 00FB, 00FC
+    XoChip,  // + 00DN, 5XY2, 5XY3, F000 NNNN
+}
+
+// Everything that differs between the CHIP-8 implementations of the past
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Platform {
+    pub name: &'static str,
+    pub quirks: Quirks,
+    pub instruction_set: InstructionSet,
+    pub stack_depth: usize,
+    pub memory_size: usize,
+    pub load_address: usize, // Where programs start, 0x200 except for e.g. ETI-660 programs at 0x600
+    pub font: &'static [u8; 80],
+    pub big_font: &'static [u8; 100],
+    pub speed: usize, // Number of instructions per 60 Hz frame
+}
+
+impl Platform {
+    // Names accepted by Platform::from_name
+    pub const NAMES: [&'static str; 6] = [
+        "cosmac-vip",
+        "chip-48",
+        "schip-1.0",
+        "schip-1.1",
+        "modern",
+        "xo-chip",
+    ];
+
+    // CHIP-8 as originally implemented on the RCA COSMAC VIP (1977)
+    pub fn cosmac_vip() -> Platform {
+        Platform {
+            name: "cosmac-vip",
+            quirks: Quirks {
+                shift_uses_vy: true,
+                load_store_increments_i: true,
+                jump_uses_vx: false,
+                logic_resets_vf: true,
+                clip_sprites: true,
+                display_wait: true,
+            },
+            instruction_set: InstructionSet::Chip8,
+            stack_depth: 12,
+            memory_size: 0x1000,
+            load_address: 0x200,
+            font: &VIP_FONT,
+            big_font: &SCHIP8_FONT,
+            speed: 15,
+        }
+    }
+
+    // CHIP-48 for the HP-48 calculators (1990)
+    pub fn chip_48() -> Platform {
+        Platform {
+            name: "chip-48",
+            quirks: Quirks {
+                shift_uses_vy: false,
+                load_store_increments_i: false,
+                jump_uses_vx: true,
+                logic_resets_vf: false,
+                clip_sprites: true,
+                display_wait: false,
+            },
+            instruction_set: InstructionSet::Chip8,
+            stack_depth: 16,
+            memory_size: 0x1000,
+            load_address: 0x200,
+            font: &CHIP8_FONT,
+            big_font: &SCHIP8_FONT,
+            speed: 30,
+        }
+    }
+
+    // SUPER-CHIP 1.0 (1991)
+    pub fn schip_1_0() -> Platform {
+        Platform {
+            name: "schip-1.0",
+            instruction_set: InstructionSet::SChip10,
+            ..Platform::chip_48()
+        }
+    }
+
+    // SUPER-CHIP 1.1 (1991)
+    pub fn schip_1_1() -> Platform {
+        Platform {
+            name: "schip-1.1",
+            instruction_set: InstructionSet::SChip11,
+            ..Platform::chip_48()
+        }
+    }
+
+    // SCHIP 1.1 instructions with the quirks most modern interpreters (and ROMs written for them) agree on
+    pub fn modern() -> Platform {
+        Platform {
+            name: "modern",
+            quirks: Quirks {
+                shift_uses_vy: true,
+                load_store_increments_i: true,
+                jump_uses_vx: false,
+                logic_resets_vf: false,
+                clip_sprites: false,
+                display_wait: false,
+            },
+            instruction_set: InstructionSet::SChip11,
+            stack_depth: 16,
+            memory_size: 0x1000,
+            load_address: 0x200,
+            font: &CHIP8_FONT,
+            big_font: &SCHIP8_FONT,
+            speed: 200,
+        }
+    }
+
+    // XO-CHIP as specified by Octo (2014), with 64 KiB of memory
+    pub fn xo_chip() -> Platform {
+        Platform {
+            name: "xo-chip",
+            instruction_set: InstructionSet::XoChip,
+            memory_size: 0x10000,
+            speed: 1000,
+            ..Platform::modern()
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Platform> {
+        match name {
+            "cosmac-vip" => Some(Platform::cosmac_vip()),
+            "chip-48" => Some(Platform::chip_48()),
+            "schip-1.0" => Some(Platform::schip_1_0()),
+            "schip-1.1" => Some(Platform::schip_1_1()),
+            "modern" => Some(Platform::modern()),
+            "xo-chip" => Some(Platform::xo_chip()),
+            _ => None,
+        }
+    }
+
+    // The fonts are not stored, they are already part of the memory
+    pub(crate) fn save(&self, writer: &mut Writer) {
+        writer.u8(self.name.len() as u8);
+        writer.bytes(self.name.as_bytes());
+        self.quirks.save(writer);
+        writer.u8(self.instruction_set as u8);
+        writer.u32(self.stack_depth as u32);
+        writer.u32(self.memory_size as u32);
+        writer.u32(self.load_address as u32);
+        writer.u32(self.speed as u32);
+    }
+
+    // Starts from the preset of the stored name, so the overridden settings are restored as well
+    pub(crate) fn load(reader: &mut Reader) -> Result<Platform, SnapshotError> {
+        let len = reader.u8()? as usize;
+        let name = std::str::from_utf8(reader.bytes(len)?)
+            .ok()
+            .and_then(Platform::from_name)
+            .ok_or(SnapshotError::Invalid("platform"))?;
+        let quirks = Quirks::load(reader)?;
+        let instruction_set = match reader.u8()? {
+            0 => InstructionSet::Chip8,
+            1 => InstructionSet::SChip10,
+            2 => InstructionSet::SChip11,
+            3 => InstructionSet::XoChip,
+            _ => return Err(SnapshotError::Invalid("instruction set")),
+        };
+        let stack_depth = reader.u32()? as usize;
+        let memory_size = reader.u32()? as usize;
+        let load_address = reader.u32()? as usize;
+        let speed = reader.u32()? as usize;
+        if !(0x200..=0x10000).contains(&memory_size) || load_address >= memory_size {
+            return Err(SnapshotError::Invalid("memory size"));
+        }
+        if !(1..=MAX_STACK_DEPTH).contains(&stack_depth) {
+            return Err(SnapshotError::Invalid("stack depth"));
+        }
+
+        Ok(Platform {
+            quirks,
+            instruction_set,
+            stack_depth,
+            memory_size,
+            load_address,
+            speed,
+            ..name
+        })
+    }
+}
+
+impl Default for Platform {
+    fn default() -> Platform {
+        Platform::schip_1_1()
+    }
+}
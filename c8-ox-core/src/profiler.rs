@@ -0,0 +1,245 @@
+use crate::hooks::Hooks;
+use crate::instruction::{decode_at, Instruction, Syntax};
+use crate::schip8::SChip8;
+use std::collections::BTreeMap;
+
+// Longest loop body, in instructions, that is still taken for a delay timer polling loop
+const MAX_POLLING_LOOP: usize = 8;
+
+// A loop closed by a jump backwards, from the target to the jump
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Loop {
+    iterations: u64,
+    polling: bool, // Only reads the delay timer and compares, like LD V0, DT / SE V0, 0 / JP loop
+}
+
+// Counts where the instructions of a program are spent: executions per address, calls per
+// subroutine, loops, and the time waiting for a key with FX0A or for the delay timer in a loop.
+// Run the machine with SChip8::run_frame_with(key, &mut profiler), then print the report.
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+    instructions: u64,
+    frames: u64,
+    hits: Vec<u64>,                        // Executions by address
+    calls: BTreeMap<usize, u64>,           // Calls by subroutine address
+    key_waits: BTreeMap<usize, u64>,       // Repetitions of FX0A waiting for a key, by address
+    loops: BTreeMap<(usize, usize), Loop>, // By the addresses of the target and the jump
+    polling_loops: Vec<(usize, usize)>,    // The loops found to poll the delay timer
+    timer_polling: u64,                    // Instructions executed in them
+    waiting_at: Option<usize>,             // Address of the FX0A executed last
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    // Addresses executed, the most executed first
+    fn ranked_hits(&self) -> Vec<(usize, u64)> {
+        let mut hits: Vec<(usize, u64)> = self
+            .hits
+            .iter()
+            .enumerate()
+            .filter(|(_, &hits)| hits > 0)
+            .map(|(addr, &hits)| (addr, hits))
+            .collect();
+        hits.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hits
+    }
+
+    fn percent(&self, count: u64) -> f64 {
+        count as f64 * 100.0 / self.instructions.max(1) as f64
+    }
+
+    // Ranked report of the count hottest instructions, subroutines and loops
+    pub fn report(&self, schip8: &SChip8, count: usize) -> String {
+        let key_wait: u64 = self.key_waits.values().sum();
+        let mut report = format!(
+            "Profile of {} instructions in {} frames\n\
+             Waiting for a key (FX0A): {} instructions ({:.1}%)\n\
+             Polling the delay timer: {} instructions ({:.1}%)\n",
+            self.instructions,
+            self.frames,
+            key_wait,
+            self.percent(key_wait),
+            self.timer_polling,
+            self.percent(self.timer_polling)
+        );
+
+        report += "\nHottest instructions:\n        Hits       %  Addr  Instruction\n";
+        for (addr, hits) in self.ranked_hits().into_iter().take(count) {
+            report += &format!(
+                "{:>12} {:>6.2}%  {:04X}  {}\n",
+                hits,
+                self.percent(hits),
+                addr,
+                disassemble(schip8, addr)
+            );
+        }
+
+        let mut calls: Vec<(&usize, &u64)> = self.calls.iter().collect();
+        calls.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        report += "\nMost called subroutines:\n       Calls  Addr\n";
+        for (addr, calls) in calls.into_iter().take(count) {
+            report += &format!("{:>12}  {:04X}\n", calls, addr);
+        }
+
+        let mut loops: Vec<(&(usize, usize), &Loop)> = self.loops.iter().collect();
+        loops.sort_by(|a, b| b.1.iterations.cmp(&a.1.iterations).then(a.0.cmp(b.0)));
+        report += "\nHottest loops:\n  Iterations  Addr\n";
+        for (&(start, end), l) in loops.into_iter().take(count) {
+            let kind = if l.polling {
+                "  polling the delay timer"
+            } else {
+                ""
+            };
+            report += &format!("{:>12}  {:04X}-{:04X}{}\n", l.iterations, start, end, kind);
+        }
+        report
+    }
+
+    // One row per executed address: addr,hits,calls,key_waits,instruction
+    pub fn to_csv(&self, schip8: &SChip8) -> String {
+        let mut csv = "addr,hits,calls,key_waits,instruction\n".to_string();
+        for (addr, &hits) in self.hits.iter().enumerate().filter(|(_, &hits)| hits > 0) {
+            // Quoted, since instructions contain commas
+            csv += &format!(
+                "{:04X},{},{},{},\"{}\"\n",
+                addr,
+                hits,
+                self.calls.get(&addr).unwrap_or(&0),
+                self.key_waits.get(&addr).unwrap_or(&0),
+                disassemble(schip8, addr)
+            );
+        }
+        csv
+    }
+
+    pub fn to_json(&self, schip8: &SChip8) -> String {
+        let addresses: Vec<String> = self
+            .hits
+            .iter()
+            .enumerate()
+            .filter(|(_, &hits)| hits > 0)
+            .map(|(addr, &hits)| {
+                format!(
+                    "{{\"addr\":{},\"hits\":{},\"instruction\":\"{}\"}}",
+                    addr,
+                    hits,
+                    disassemble(schip8, addr)
+                )
+            })
+            .collect();
+        let calls: Vec<String> = self
+            .calls
+            .iter()
+            .map(|(addr, calls)| format!("{{\"addr\":{},\"calls\":{}}}", addr, calls))
+            .collect();
+        let key_waits: Vec<String> = self
+            .key_waits
+            .iter()
+            .map(|(addr, waits)| format!("{{\"addr\":{},\"instructions\":{}}}", addr, waits))
+            .collect();
+        let loops: Vec<String> = self
+            .loops
+            .iter()
+            .map(|((start, end), l)| {
+                format!(
+                    "{{\"start\":{},\"end\":{},\"iterations\":{},\"timer_polling\":{}}}",
+                    start, end, l.iterations, l.polling
+                )
+            })
+            .collect();
+        format!(
+            "{{\"instructions\":{},\"frames\":{},\"timer_polling\":{},\"addresses\":[{}],\
+             \"calls\":[{}],\"key_waits\":[{}],\"loops\":[{}]}}\n",
+            self.instructions,
+            self.frames,
+            self.timer_polling,
+            addresses.join(","),
+            calls.join(","),
+            key_waits.join(","),
+            loops.join(",")
+        )
+    }
+}
+
+impl Hooks for Profiler {
+    fn before_instruction(&mut self, schip8: &SChip8) -> bool {
+        let pc = schip8.pc();
+        self.instructions += 1;
+        if pc >= self.hits.len() {
+            self.hits.resize(pc + 1, 0);
+        }
+        self.hits[pc] += 1;
+
+        // FX0A without a key stays at its address
+        if self.waiting_at.take() == Some(pc) {
+            *self.key_waits.entry(pc).or_insert(0) += 1;
+        }
+        if self
+            .polling_loops
+            .iter()
+            .any(|&(start, end)| (start..=end).contains(&pc))
+        {
+            self.timer_polling += 1;
+        }
+
+        match decode_at(schip8.memory(), pc) {
+            Some(Instruction::WaitKey(_)) => self.waiting_at = Some(pc),
+            Some(Instruction::Call(addr)) => *self.calls.entry(addr as usize).or_insert(0) += 1,
+            Some(Instruction::Jump(addr)) if addr as usize <= pc => {
+                let start = addr as usize;
+                let polling_loops = &mut self.polling_loops;
+                let l = self.loops.entry((start, pc)).or_insert_with(|| {
+                    let polling = polls_delay_timer(schip8, start, pc);
+                    if polling {
+                        polling_loops.push((start, pc));
+                    }
+                    Loop {
+                        iterations: 0,
+                        polling,
+                    }
+                });
+                l.iterations += 1;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn frame_finished(&mut self, _schip8: &SChip8) {
+        self.frames += 1;
+    }
+}
+
+fn disassemble(schip8: &SChip8, addr: usize) -> String {
+    decode_at(schip8.memory(), addr)
+        .map_or_else(String::new, |i| i.format(Syntax::Mnemonic, &|_| None))
+}
+
+// Whether the loop from start to the jump at end only waits for the delay timer: it reads it with
+// FX07, and otherwise just compares and moves registers
+fn polls_delay_timer(schip8: &SChip8, start: usize, end: usize) -> bool {
+    use Instruction::*;
+
+    let mut reads_timer = false;
+    let mut addr = start;
+    for _ in 0..MAX_POLLING_LOOP {
+        let instruction = match decode_at(schip8.memory(), addr) {
+            Some(instruction) => instruction,
+            None => return false,
+        };
+        if addr == end {
+            return reads_timer;
+        }
+        match instruction {
+            GetDelay(_) => reads_timer = true,
+            SkipEqualByte(..) | SkipNotEqualByte(..) | SkipEqual(..) | SkipNotEqual(..)
+            | LoadByte(..) | AddByte(..) | Move(..) | And(..) | Sub(..) | SubReverse(..) => {}
+            _ => return false,
+        }
+        addr += instruction.size();
+    }
+    false
+}
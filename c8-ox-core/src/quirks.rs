@@ -0,0 +1,67 @@
+use crate::snapshot::{Reader, SnapshotError, Writer};
+
+// Toggles for the opcodes whose semantics differ between the CHIP-8 implementations of the past.
+// See https://chip-8.github.io/extensions/ for where each of them comes from.
+// The default matches the behaviour the interpreter always had (roughly SCHIP 1.1).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY6/8XYE shift VY and store the result in VX (COSMAC VIP) instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    // FX55/FX65 leave I pointing behind the last register stored or loaded (COSMAC VIP)
+    pub load_store_increments_i: bool,
+    // BNNN is read as BXNN and jumps to XNN plus VX (CHIP-48, SCHIP) instead of NNN plus V0
+    pub jump_uses_vx: bool,
+    // 8XY1/8XY2/8XY3 reset VF to 0 (COSMAC VIP)
+    pub logic_resets_vf: bool,
+    // Sprites are cut off at the screen edges instead of wrapping around to the other side
+    pub clip_sprites: bool,
+    // DXYN waits for the next 60 Hz vertical blank before drawing (COSMAC VIP)
+    pub display_wait: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+}
+
+impl Quirks {
+    // Stored as one bit per quirk, in the order of declaration
+    pub(crate) fn save(&self, writer: &mut Writer) {
+        let quirks = [
+            self.shift_uses_vy,
+            self.load_store_increments_i,
+            self.jump_uses_vx,
+            self.logic_resets_vf,
+            self.clip_sprites,
+            self.display_wait,
+        ];
+        let bits = quirks
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &quirk)| bits | ((quirk as u8) << i));
+        writer.u8(bits);
+    }
+
+    pub(crate) fn load(reader: &mut Reader) -> Result<Quirks, SnapshotError> {
+        let bits = reader.u8()?;
+        if bits >> 6 != 0 {
+            return Err(SnapshotError::Invalid("quirk"));
+        }
+        Ok(Quirks {
+            shift_uses_vy: bits & 0x01 != 0,
+            load_store_increments_i: bits & 0x02 != 0,
+            jump_uses_vx: bits & 0x04 != 0,
+            logic_resets_vf: bits & 0x08 != 0,
+            clip_sprites: bits & 0x10 != 0,
+            display_wait: bits & 0x20 != 0,
+        })
+    }
+}
@@ -0,0 +1,48 @@
+// Source of random numbers for the CXNN instruction.
+// The interpreter only asks for bytes through this trait, so a frontend or test can inject its own
+// source. SeededRandom is the default, which makes runs reproducible from the seed alone.
+pub trait Random {
+    // Returns a uniformly distributed byte
+    fn random_u8(&mut self) -> u8;
+
+    // Internal state, stored in snapshots so a restored machine continues with the same numbers
+    fn state(&self) -> u64;
+
+    fn set_state(&mut self, state: u64);
+}
+
+// SplitMix64 generator, small and fast with good enough statistics for games.
+// The same seed yields the same sequence on every platform and frontend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeededRandom {
+    state: u64,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> SeededRandom {
+        SeededRandom { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl Random for SeededRandom {
+    fn random_u8(&mut self) -> u8 {
+        // The high bits are the best mixed ones
+        (self.next_u64() >> 56) as u8
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.state = state;
+    }
+}
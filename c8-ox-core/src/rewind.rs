@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+
+// History of the last frames for stepping backwards in time.
+// Only the newest snapshot is kept whole. Every older one is stored as the difference to its successor,
+// XORed and run-length encoded, which shrinks a frame to a few bytes as little changes between frames.
+pub struct Rewind {
+    capacity: usize,           // Number of frames that can be stepped back
+    latest: Vec<u8>,           // Newest snapshot
+    deltas: VecDeque<Vec<u8>>, // Oldest first, each turns its successor into the snapshot before it
+}
+
+impl Rewind {
+    pub fn new(capacity: usize) -> Rewind {
+        Rewind {
+            capacity,
+            latest: Vec::new(),
+            deltas: VecDeque::new(),
+        }
+    }
+
+    // Number of frames that can currently be stepped back
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest.clear();
+        self.deltas.clear();
+    }
+
+    // Records the snapshot of a new frame, dropping the oldest one once the capacity is reached
+    pub fn push(&mut self, snapshot: Vec<u8>) {
+        if self.latest.len() == snapshot.len() {
+            self.deltas.push_back(encode(&snapshot, &self.latest));
+            if self.deltas.len() > self.capacity {
+                self.deltas.pop_front();
+            }
+        } else {
+            // Snapshots of a different platform cannot be diffed, the history starts anew
+            self.deltas.clear();
+        }
+        self.latest = snapshot;
+    }
+
+    // Discards the newest snapshot and returns the one before, for SChip8::restore
+    pub fn step_back(&mut self) -> Option<&[u8]> {
+        let delta = self.deltas.pop_back()?;
+        decode(&delta, &mut self.latest);
+        Some(&self.latest)
+    }
+}
+
+// Encodes old XOR new as pairs of an unchanged run and a changed run, both lengths as varints,
+// followed by the XORed bytes of the changed run
+fn encode(new: &[u8], old: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    let mut i = 0;
+    while i < new.len() {
+        let start = i;
+        while i < new.len() && new[i] == old[i] {
+            i += 1;
+        }
+        let unchanged = i - start;
+
+        let start = i;
+        while i < new.len() && new[i] != old[i] {
+            i += 1;
+        }
+        write_varint(&mut delta, unchanged);
+        write_varint(&mut delta, i - start);
+        delta.extend(new[start..i].iter().zip(&old[start..i]).map(|(n, o)| n ^ o));
+    }
+    delta
+}
+
+// Applies a delta created by encode to the new snapshot, turning it back into the old one
+fn decode(delta: &[u8], snapshot: &mut [u8]) {
+    let mut delta = delta.iter();
+    let mut i = 0;
+    while let Some(unchanged) = read_varint(&mut delta) {
+        i += unchanged;
+        let changed = read_varint(&mut delta).unwrap_or(0);
+        for (byte, diff) in snapshot[i..i + changed].iter_mut().zip(&mut delta) {
+            *byte ^= diff;
+        }
+        i += changed;
+    }
+}
+
+// LEB128, 7 bits per byte with the high bit set on all but the last byte
+fn write_varint(data: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        data.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
+
+fn read_varint<'a, I: Iterator<Item = &'a u8>>(data: &mut I) -> Option<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *data.next()?;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Snapshots of the given size that each change a few scattered bytes and one long run of the
+    // previous one, so that both short and multi-byte varint runs occur
+    fn snapshots(count: usize, size: usize) -> Vec<Vec<u8>> {
+        let mut seed = 0x1234_5678u32;
+        let mut random = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as usize
+        };
+        let mut snapshot = vec![0u8; size];
+        let mut snapshots = Vec::new();
+        for frame in 0..count {
+            for _ in 0..random() % 8 {
+                let i = random() % size;
+                snapshot[i] = snapshot[i].wrapping_add(1 + frame as u8);
+            }
+            let start = random() % size;
+            let end = (start + random() % 300).min(size);
+            for byte in snapshot[start..end].iter_mut() {
+                *byte ^= 0xA5;
+            }
+            snapshots.push(snapshot.clone());
+        }
+        // Frames that change nothing
+        snapshots.push(snapshot.clone());
+        snapshots.push(snapshot);
+        snapshots
+    }
+
+    #[test]
+    fn steps_back_through_every_snapshot() {
+        let snapshots = snapshots(40, 4096);
+        let mut rewind = Rewind::new(100);
+        for snapshot in snapshots.iter() {
+            rewind.push(snapshot.clone());
+        }
+        assert_eq!(rewind.len(), snapshots.len() - 1);
+        for snapshot in snapshots.iter().rev().skip(1) {
+            assert_eq!(rewind.step_back(), Some(snapshot.as_slice()));
+        }
+        assert_eq!(rewind.step_back(), None);
+        assert!(rewind.is_empty());
+    }
+
+    #[test]
+    fn drops_the_oldest_snapshots_beyond_the_capacity() {
+        let snapshots = snapshots(20, 1000);
+        let mut rewind = Rewind::new(5);
+        for snapshot in snapshots.iter() {
+            rewind.push(snapshot.clone());
+        }
+        assert_eq!(rewind.len(), 5);
+        for snapshot in snapshots.iter().rev().skip(1).take(5) {
+            assert_eq!(rewind.step_back(), Some(snapshot.as_slice()));
+        }
+        assert_eq!(rewind.step_back(), None);
+    }
+
+    #[test]
+    fn starts_anew_when_the_snapshot_size_changes() {
+        let small = snapshots(3, 500);
+        let large = snapshots(4, 800);
+        let mut rewind = Rewind::new(10);
+        for snapshot in small.iter().chain(large.iter()) {
+            rewind.push(snapshot.clone());
+        }
+        assert_eq!(rewind.len(), large.len() - 1);
+        for snapshot in large.iter().rev().skip(1) {
+            assert_eq!(rewind.step_back(), Some(snapshot.as_slice()));
+        }
+        assert_eq!(rewind.step_back(), None);
+
+        // Stepping back still works after pushing on top of a rewound history
+        rewind.push(small[0].clone());
+        rewind.push(small[1].clone());
+        assert_eq!(rewind.step_back(), Some(small[0].as_slice()));
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for &value in [0, 1, 0x7F, 0x80, 0x3FFF, 0x4000, 123_456_789].iter() {
+            let mut data = Vec::new();
+            write_varint(&mut data, value);
+            assert_eq!(read_varint(&mut data.iter()), Some(value));
+        }
+    }
+}
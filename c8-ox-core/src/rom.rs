@@ -0,0 +1,92 @@
+use crate::platform::Platform;
+use std::{error, fmt, fs, io, path::Path};
+
+// Why a ROM could not be loaded
+#[derive(Debug)]
+pub enum RomError {
+    // The file could not be read
+    Unreadable(io::Error),
+    // The file contains no program
+    Empty,
+    // The program does not fit into the memory above the load address of the platform
+    TooLarge { size: usize, max_size: usize },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::Unreadable(error) => write!(f, "unable to read ROM: {}", error),
+            RomError::Empty => write!(f, "ROM is empty"),
+            RomError::TooLarge { size, max_size } => write!(
+                f,
+                "ROM is {} byte long, but only {} byte fit into memory",
+                size, max_size
+            ),
+        }
+    }
+}
+
+impl error::Error for RomError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RomError::Unreadable(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(error: io::Error) -> RomError {
+        RomError::Unreadable(error)
+    }
+}
+
+// A program image together with its metadata
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rom {
+    data: Vec<u8>,
+    sha1: [u8; 20],
+}
+
+impl Rom {
+    pub fn new(data: Vec<u8>) -> Result<Rom, RomError> {
+        if data.is_empty() {
+            return Err(RomError::Empty);
+        }
+        let sha1 = sha1_smol::Sha1::from(&data).digest().bytes();
+        Ok(Rom { data, sha1 })
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Rom, RomError> {
+        Rom::new(fs::read(path)?)
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn sha1(&self) -> &[u8; 20] {
+        &self.sha1
+    }
+
+    // SHA-1 as lowercase hex string, e.g. for file names or looking the ROM up in databases
+    pub fn sha1_hex(&self) -> String {
+        self.sha1.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    // Fails if the ROM does not fit into the memory of the platform above its load address
+    pub fn check_size(&self, platform: &Platform) -> Result<(), RomError> {
+        let max_size = platform.memory_size.saturating_sub(platform.load_address);
+        if self.size() > max_size {
+            return Err(RomError::TooLarge {
+                size: self.size(),
+                max_size,
+            });
+        }
+        Ok(())
+    }
+}
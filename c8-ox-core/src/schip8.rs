@@ -0,0 +1,987 @@
+use crate::audio::{Audio, PATTERN_BITS};
+use crate::display::Display;
+use crate::error::{ExecutionError, Fault};
+use crate::flags::FlagStorage;
+use crate::hooks::{Access, Hooks, NoHooks};
+use crate::instruction::{decode, Instruction};
+use crate::platform::{InstructionSet, Platform};
+use crate::random::Random;
+use crate::rom::{Rom, RomError};
+use crate::snapshot::{Reader, SnapshotError, Writer, MAGIC, SNAPSHOT_VERSION};
+
+pub struct SChip8 {
+    pc: usize,               //
+    ar: u16,                 // Address register
+    sp: usize,               //
+    r: [u8; 16],             // RPL Flags, 8 for SCHIP8 and 16 for XO-CHIP
+    v: [u16; 16],            //
+    dt: u8,                  // Delay timer
+    st: u8,                  // Sound timer
+    stack: Vec<usize>,       // Stack implemented as empty ascending
+    ram: Vec<u8>,            // 4 KiB, 64 KiB for XO-CHIP
+    display: Display,        // Bitplanes and screen mode
+    audio: Audio,            // XO-CHIP sound pattern and pitch
+    key_pad: [bool; 16],     //
+    platform: Platform,      // Quirks, opcodes and limits of the emulated machine
+    vblank: bool,            // Set on every 60 Hz tick, used by the display wait quirk
+    frame_cycles: usize,     // Instructions executed in the current 60 Hz frame
+    random: Box<dyn Random>, // Source for CXNN, usually a SeededRandom
+    // Keeps the RPL flags between runs
+    flag_storage: Option<Box<dyn FlagStorage>>,
+}
+
+impl SChip8 {
+    // Fails if the ROM does not fit into the memory of the platform
+    pub fn new(
+        rom: &Rom,
+        platform: &Platform,
+        random: Box<dyn Random>,
+    ) -> Result<SChip8, RomError> {
+        rom.check_size(platform)?;
+
+        let mut schip8 = SChip8 {
+            pc: platform.load_address,
+            ar: 0,
+            sp: 0,
+            r: [0; 16],
+            v: [0; 16],
+            dt: 0,
+            st: 0,
+            stack: vec![0; platform.stack_depth],
+            ram: vec![0; platform.memory_size],
+            display: Display::new(),
+            audio: Audio::new(),
+            key_pad: [false; 16],
+            platform: platform.clone(),
+            vblank: false,
+            frame_cycles: 0,
+            random,
+            flag_storage: None,
+        };
+
+        let (reserved, ram) = schip8.ram.split_at_mut(platform.load_address);
+        assert!(reserved.len() == platform.load_address);
+        let (ram_l, _ram_r) = ram.split_at_mut(rom.size());
+        ram_l.copy_from_slice(rom.data());
+
+        // Insert font data
+        let mut chip8_font_area = schip8.ram.split_at_mut(80);
+        assert!(chip8_font_area.0.len() == 80);
+        chip8_font_area.0.copy_from_slice(platform.font);
+
+        chip8_font_area = schip8.ram.split_at_mut(80);
+        let schip8_font_area = chip8_font_area.1.split_at_mut(100);
+        assert!(schip8_font_area.0.len() == 100);
+        schip8_font_area.0.copy_from_slice(platform.big_font);
+
+        Ok(schip8)
+    }
+
+    // Runs one 60 Hz frame: the remaining instructions per frame of the platform, then a timer tick.
+    // key is the key pressed since the last frame (16 or more if none was), which FX0A waits for.
+    pub fn run_frame(&mut self, key: usize) -> Result<Frame, ExecutionError> {
+        self.run_frame_with(key, &mut NoHooks)
+    }
+
+    // Like run_frame, calling the hooks on the way. If they stop the frame before it ended,
+    // the next call continues it.
+    pub fn run_frame_with<H: Hooks>(
+        &mut self,
+        key: usize,
+        hooks: &mut H,
+    ) -> Result<Frame, ExecutionError> {
+        let mut redraw = false;
+        while self.frame_cycles < self.platform.speed {
+            if !hooks.before_instruction(self) {
+                return Ok(Frame {
+                    redraw,
+                    sound: self.st > 0,
+                    exited: false,
+                    stopped: true,
+                });
+            }
+            if !self.run_with(key, &mut redraw, hooks)? {
+                return Ok(Frame {
+                    redraw,
+                    sound: false,
+                    exited: true,
+                    stopped: false,
+                });
+            }
+        }
+        self.tick();
+        hooks.frame_finished(self);
+
+        Ok(Frame {
+            redraw,
+            sound: self.st > 0,
+            exited: false,
+            stopped: false,
+        })
+    }
+
+    // Executes a single instruction, returns false once the program exited.
+    // On a fault the machine is left as it was before the instruction.
+    pub fn run(&mut self, key: usize, redraw: &mut bool) -> Result<bool, ExecutionError> {
+        self.run_with(key, redraw, &mut NoHooks)
+    }
+
+    fn run_with<H: Hooks>(
+        &mut self,
+        key: usize,
+        redraw: &mut bool,
+        hooks: &mut H,
+    ) -> Result<bool, ExecutionError> {
+        let pc = self.pc;
+        if pc + 2 > self.ram.len() {
+            return Err(ExecutionError {
+                pc,
+                opcode: 0,
+                fault: Fault::PcOutOfBounds,
+            });
+        }
+        let opcode = self.read_u16(pc);
+        let instruction = decode(opcode);
+
+        // The program counter points to the next instruction while executing
+        self.pc += 2;
+        self.frame_cycles += 1;
+        let running = match self.execute(instruction, key, redraw, hooks) {
+            Ok(running) => running,
+            Err(fault) => {
+                self.pc = pc;
+                self.frame_cycles -= 1;
+                return Err(ExecutionError { pc, opcode, fault });
+            }
+        };
+
+        Ok(running)
+    }
+
+    fn execute<H: Hooks>(
+        &mut self,
+        instruction: Instruction,
+        key: usize,
+        redraw: &mut bool,
+        hooks: &mut H,
+    ) -> Result<bool, Fault> {
+        use Instruction::*;
+
+        if !self.supports(instruction.instruction_set()) {
+            // The newer instructions starting with 0 were read as machine code calls before
+            if let ScrollDown(_) | ScrollUp(_) | ScrollRight | ScrollLeft | Exit | LowRes
+            | HighRes = instruction
+            {
+                return Err(Fault::MachineCodeCall);
+            }
+            return Err(Fault::UnknownInstruction);
+        }
+
+        match instruction {
+            // 00CN - Scroll display N lines down
+            ScrollDown(n) => {
+                self.display.scroll(0, n as isize);
+                *redraw = true;
+            }
+            // 00DN - Scroll display N lines up (XO-CHIP)
+            ScrollUp(n) => {
+                self.display.scroll(0, -(n as isize));
+                *redraw = true;
+            }
+            // 00E0 - Clears the screen. (Only the selected planes for XO-CHIP)
+            Clear => {
+                self.display.clear();
+                *redraw = true;
+            }
+            // 00EE - Returns from a subroutine.
+            Return => {
+                if self.sp == 0 {
+                    return Err(Fault::StackUnderflow);
+                }
+                self.sp -= 1;
+                self.pc = self.stack[self.sp];
+            }
+            // 00FB - Scroll display 4 pixels right
+            ScrollRight => {
+                self.display.scroll(4, 0);
+                *redraw = true;
+            }
+            // 00FC - Scroll display 4 pixels left
+            ScrollLeft => {
+                self.display.scroll(-4, 0);
+                *redraw = true;
+            }
+            // 00FD - Exit CHIP interpreter
+            Exit => {
+                self.pc -= 2;
+                return Ok(false);
+            }
+            // 00FE - Disable extended screen mode
+            LowRes => {
+                self.display.set_extended(false);
+                *redraw = true;
+            }
+            // 00FF - Enable extended screen mode for full-screen graphics
+            HighRes => {
+                self.display.set_extended(true);
+                *redraw = true;
+            }
+            // 0NNN - Calls RCA 1802 program at address NNN. Not necessary for most ROMs.
+            // See issue.
+            MachineCall(_) => {
+                return Err(Fault::MachineCodeCall);
+            }
+            // 1NNN - Jumps to address NNN.
+            Jump(addr) => {
+                self.pc = addr as usize;
+            }
+            // 2NNN - Calls subroutine at NNN.
+            Call(addr) => {
+                if self.sp == self.stack.len() {
+                    return Err(Fault::StackOverflow);
+                }
+                self.stack[self.sp] = self.pc;
+                self.sp += 1;
+                self.pc = addr as usize;
+            }
+            // 3XNN - Skips the next instruction if VX equals NN. (Usually the next instruction is a jump to skip a code block)
+            SkipEqualByte(x, nn) => {
+                if self.v[x as usize] == nn as u16 {
+                    self.skip();
+                }
+            }
+            // 4XNN - Skips the next instruction if VX doesn't equal NN. (Usually the next instruction is a jump to skip a code block)
+            SkipNotEqualByte(x, nn) => {
+                if self.v[x as usize] != nn as u16 {
+                    self.skip();
+                }
+            }
+            // 5XNN - Skips the next instruction if VX equals VY. (Usually the next instruction is a jump to skip a code block)
+            SkipEqual(x, y) => {
+                if self.v[x as usize] == self.v[y as usize] {
+                    self.skip();
+                }
+            }
+            // 5XY2 - Stores VX to VY (in either order, including both) in memory starting at address I. I is left unmodified. (XO-CHIP)
+            StoreRange(x, y) => {
+                let ar = self.ar as usize;
+                let len = SChip8::register_range(x, y).count();
+                self.check_memory(ar, len)?;
+                hooks.memory_access(ar, len, Access::Write);
+                for (offset, r) in SChip8::register_range(x, y).enumerate() {
+                    self.ram[ar + offset] = self.v[r] as u8;
+                }
+            }
+            // 5XY3 - Fills VX to VY (in either order, including both) with values from memory starting at address I. I is left unmodified. (XO-CHIP)
+            LoadRange(x, y) => {
+                let ar = self.ar as usize;
+                let len = SChip8::register_range(x, y).count();
+                self.check_memory(ar, len)?;
+                hooks.memory_access(ar, len, Access::Read);
+                for (offset, r) in SChip8::register_range(x, y).enumerate() {
+                    self.v[r] = self.ram[ar + offset] as u16;
+                }
+            }
+            // 6XNN - Sets VX to NN.
+            LoadByte(x, nn) => {
+                self.v[x as usize] = nn as u16;
+            }
+            // 7XNN - Adds NN to VX. (Carry flag is not changed)
+            AddByte(x, nn) => {
+                let sum = self.v[x as usize] + nn as u16;
+                self.v[x as usize] = sum & 0xFF;
+            }
+            // 8XY0 - Sets VX to the value of VY.
+            Move(x, y) => {
+                self.v[x as usize] = self.v[y as usize];
+            }
+            // 8XY1 - Sets VX to VX or VY. (Bitwise OR operation)
+            Or(x, y) => {
+                self.v[x as usize] |= self.v[y as usize];
+                if self.platform.quirks.logic_resets_vf {
+                    self.v[0xF] = 0;
+                }
+            }
+            // 8XY2 - Sets VX to VX and VY. (Bitwise AND operation)
+            And(x, y) => {
+                self.v[x as usize] &= self.v[y as usize];
+                if self.platform.quirks.logic_resets_vf {
+                    self.v[0xF] = 0;
+                }
+            }
+            // 8XY3 - Sets VX to VX xor VY.
+            Xor(x, y) => {
+                self.v[x as usize] ^= self.v[y as usize];
+                if self.platform.quirks.logic_resets_vf {
+                    self.v[0xF] = 0;
+                }
+            }
+            // 8XY4 - Adds VY to VX. VF is set to 1 when there's a carry, and to 0 when there isn't.
+            Add(x, y) => {
+                let sum = self.v[x as usize] + self.v[y as usize];
+                if sum < 0x100 {
+                    self.v[0xF] = 0;
+                } else {
+                    self.v[0xF] = 1;
+                }
+                self.v[x as usize] = sum & 0xFF;
+            }
+            // 8XY5 - VY is subtracted from VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
+            Sub(x, y) => {
+                let mut diff = (self.v[x as usize] as i32) - (self.v[y as usize] as i32);
+                if diff >= 0 {
+                    self.v[0xF] = 1;
+                } else {
+                    self.v[0xF] = 0;
+                    diff = -diff;
+                }
+                self.v[x as usize] = diff as u16;
+            }
+            // 8XY6 - Stores the least significant bit of VX in VF and then shifts VX to the right by 1.
+            // With the shift quirk, VY is shifted and the result is stored in VX.
+            ShiftRight(x, y) => {
+                let src = self.shift_source(x, y);
+                self.v[0xF] = src & 0x1;
+                self.v[x as usize] = src >> 1;
+            }
+            // 8XY7 - Sets VX to VY minus VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
+            SubReverse(x, y) => {
+                let mut diff = (self.v[y as usize] as i32) - (self.v[x as usize] as i32);
+                if diff >= 0 {
+                    self.v[0xF] = 1;
+                } else {
+                    self.v[0xF] = 0;
+                    diff = -diff;
+                }
+                self.v[x as usize] = diff as u16;
+            }
+            // 8XYE - Stores the most significant bit of VX in VF and then shifts VX to the left by 1.
+            // With the shift quirk, VY is shifted and the result is stored in VX.
+            ShiftLeft(x, y) => {
+                let src = self.shift_source(x, y);
+                self.v[0xF] = (src & 0x80) >> 7;
+                self.v[x as usize] = (src << 1) & 0xFF;
+            }
+            // 9XY0 - Skips the next instruction if VX doesn't equal VY.
+            SkipNotEqual(x, y) => {
+                if self.v[x as usize] != self.v[y as usize] {
+                    self.skip();
+                }
+            }
+            // ANNN - Sets I to the address NNN.
+            LoadI(addr) => {
+                self.ar = addr;
+            }
+            // BNNN - Jumps to the address NNN plus V0.
+            // With the jump quirk this is BXNN, which jumps to XNN plus VX.
+            JumpOffset(addr) => {
+                let mut addr = addr as usize;
+                if self.platform.quirks.jump_uses_vx {
+                    addr += self.v[addr >> 8] as usize;
+                } else {
+                    addr += self.v[0] as usize;
+                }
+                self.pc = addr;
+            }
+            // CXNN - Sets VX to the result of a bitwise and operation on a random number (Typically: 0 to 255) and NN.
+            Random(x, nn) => {
+                let rand = self.random.random_u8();
+                self.v[x as usize] = (rand & nn) as u16;
+            }
+            // DXYN - Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels and a height of N pixels. Each row of 8 pixels is read as bit-coded starting from memory location I; I value doesn’t change after the execution of this instruction. As described above, VF is set to 1 if any screen pixels are flipped from set to unset when the sprite is drawn, and to 0 if that doesn’t happen
+            // With the display wait quirk, the instruction is repeated until the next vertical blank.
+            Draw(x, y, n) => {
+                if self.platform.quirks.display_wait && !self.vblank {
+                    self.pc -= 2;
+                } else {
+                    self.render(x, y, n, hooks)?;
+                    self.vblank = false;
+                    *redraw = true;
+                }
+            }
+            // EX9E - Skips the next instruction if the key stored in VX is pressed. (Usually the next instruction is a jump to skip a code block)
+            SkipKey(x) => {
+                let vx = self.v[x as usize] & 0xF;
+                let keyp = self.key_pad[vx as usize];
+
+                if keyp {
+                    self.skip();
+                }
+            }
+            // EXA1 - Skips the next instruction if the key stored in VX isn't pressed. (Usually the next instruction is a jump to skip a code block)
+            SkipNotKey(x) => {
+                let vx = self.v[x as usize] & 0xF;
+                let keyp = self.key_pad[vx as usize];
+
+                if !keyp {
+                    self.skip();
+                }
+            }
+            // FN01 - Selects the bitplanes N (0 to 3) drawn to by DXYN, 00E0 and the scroll instructions. (XO-CHIP)
+            SelectPlanes(n) => {
+                self.display.select_planes(n);
+            }
+            // F002 - Loads the 16 byte audio pattern from memory starting at address I. (XO-CHIP)
+            LoadAudio => {
+                let ar = self.ar as usize;
+                self.check_memory(ar, PATTERN_BITS / 8)?;
+                hooks.memory_access(ar, PATTERN_BITS / 8, Access::Read);
+                self.audio.set_pattern(&self.ram[ar..ar + PATTERN_BITS / 8]);
+            }
+            // F000 NNNN - Sets I to the 16 bit address NNNN stored in the following two bytes. (XO-CHIP)
+            LoadLongI(_) => {
+                self.check_memory(self.pc, 2)?;
+                self.ar = self.read_u16(self.pc);
+                self.pc += 2;
+            }
+            // FX07 - Sets VX to the value of the delay timer.
+            GetDelay(x) => {
+                self.v[x as usize] = self.dt as u16;
+            }
+            // FX0A - A key press is awaited, and then stored in VX. (Blocking Operation. All instruction halted until next key event)
+            WaitKey(x) => {
+                if key < 16 {
+                    self.v[x as usize] = key as u16;
+                } else {
+                    self.pc -= 2;
+                }
+            }
+            // FX15 - Sets the delay timer to VX.
+            SetDelay(x) => {
+                self.dt = self.v[x as usize] as u8;
+            }
+            // FX18 - Sets the sound timer to VX.
+            SetSound(x) => {
+                self.st = self.v[x as usize] as u8;
+            }
+            // FX1E - Adds VX to I. VF is set to 1 when there is a range overflow (I+VX>0xFFF, 0xFFFF for XO-CHIP), and to 0 when there isn't.
+            AddI(x) => {
+                let sum = self.ar as usize + self.v[x as usize] as usize;
+                if sum >= self.ram.len() {
+                    self.v[0xF] = 1;
+                } else {
+                    self.v[0xF] = 0;
+                }
+                self.ar = (sum % self.ram.len()) as u16;
+            }
+            // FX29 - Sets I to the location of the sprite for the character in VX. Characters 0-F (in hexadecimal) are represented by a 4x5 font.
+            Font(x) => {
+                self.ar = self.v[x as usize] * 5;
+            }
+            // FX30 - Point I to 10-byte font sprite for digit VX (0..9)
+            BigFont(x) => {
+                self.ar = 80 + self.v[x as usize] * 10;
+            }
+            // FX3A - Sets the pitch register to VX, the pattern is played at 4000*2^((VX-64)/48) bits per second. (XO-CHIP)
+            SetPitch(x) => {
+                self.audio.set_pitch(self.v[x as usize] as u8);
+            }
+            // FX33 - Stores the binary-coded decimal representation of VX, with the most significant of three digits at the address in I, the middle digit at I plus 1, and the least significant digit at I plus 2. (In other words, take the decimal representation of VX, place the hundreds digit in memory at location in I, the tens digit at location I+1, and the ones digit at location I+2.)
+            Bcd(x) => {
+                let ar = self.ar as usize;
+                self.check_memory(ar, 3)?;
+                hooks.memory_access(ar, 3, Access::Write);
+                let vx = self.v[x as usize];
+                self.ram[ar] = ((vx - (vx % 100)) / 100) as u8;
+                self.ram[ar + 1] = ((vx - vx % 10) / 10) as u8;
+                self.ram[ar + 2] = (vx % 10) as u8;
+            }
+            // FX55 - Stores V0 to VX (including VX) in memory starting at address I. The offset from I is increased by 1 for each value written, but I itself is left unmodified.
+            // With the load/store quirk, I is left pointing behind the last value written.
+            Store(x) => {
+                let ar = self.ar as usize;
+                self.check_memory(ar, x as usize + 1)?;
+                hooks.memory_access(ar, x as usize + 1, Access::Write);
+                let mut xi = 0;
+                while xi <= (x as usize) {
+                    self.ram[ar + xi] = self.v[xi] as u8;
+                    xi += 1;
+                }
+                if self.platform.quirks.load_store_increments_i {
+                    self.ar = self.ar.wrapping_add(x as u16 + 1);
+                }
+            }
+            // FX65 - Fills V0 to VX (including VX) with values from memory starting at address I. The offset from I is increased by 1 for each value written, but I itself is left unmodified.
+            // With the load/store quirk, I is left pointing behind the last value read.
+            Load(x) => {
+                let ar = self.ar as usize;
+                self.check_memory(ar, x as usize + 1)?;
+                hooks.memory_access(ar, x as usize + 1, Access::Read);
+                let mut xi = 0;
+                while xi <= (x as usize) {
+                    self.v[xi] = self.ram[ar + xi] as u16;
+                    xi += 1;
+                }
+                if self.platform.quirks.load_store_increments_i {
+                    self.ar = self.ar.wrapping_add(x as u16 + 1);
+                }
+            }
+            // FX75 - Store V0..VX in RPL user flags (X <= 7, X <= F for XO-CHIP)
+            StoreFlags(x) if (x as usize) < self.flag_count() => {
+                let old = self.r;
+                for i in 0..(x as usize) + 1 {
+                    self.r[i] = self.v[i] as u8;
+                }
+                if let (Some(storage), true) = (&mut self.flag_storage, self.r != old) {
+                    storage.save(&self.r);
+                }
+            }
+            // FX85 - Read V0..VX from RPL user flags (X <= 7, X <= F for XO-CHIP)
+            LoadFlags(x) if (x as usize) < self.flag_count() => {
+                for i in 0..(x as usize) + 1 {
+                    self.v[i] = self.r[i] as u16;
+                }
+            }
+            StoreFlags(_) | LoadFlags(_) | Unknown(_) => {
+                return Err(Fault::UnknownInstruction);
+            }
+        }
+
+        Ok(true)
+    }
+
+    // - Coordinate (VX, VY)                            - Check
+    // - 8x{1-F} sprite, starts at I                    - Check
+    // - Each row bit coded                             - Check
+    // - I does not change                              - Check
+    // - Flip from set to unset => VF=1, otherwise VF=0 - Check
+    // - Wrap around or clip at the edges (clip quirk)  - Check
+    // For SCHIP8: Show N-byte sprite from M(I) at coords (VX,VY), VF := collision. If N=0 and extended mode, show 16x16 sprite.
+    // For XO-CHIP: Draw to every selected plane, the sprite data of each following the one of the previous plane.
+    fn render<H: Hooks>(&mut self, x: u8, y: u8, c: u8, hooks: &mut H) -> Result<(), Fault> {
+        // 16x16 sprites consist of two bytes per row, XO-CHIP draws them in both screen modes
+        let big_sprite = self.display.extended() || self.supports(InstructionSet::XoChip);
+        let (bytes_per_row, height) = if c == 0 && big_sprite {
+            (2, 16)
+        } else {
+            (1, c as usize)
+        };
+
+        let x = (self.v[x as usize] as usize) % self.display.width();
+        let y = (self.v[y as usize] as usize) % self.display.height();
+        let clip = self.platform.quirks.clip_sprites;
+
+        let planes = self.display.planes();
+        let mut ar = self.ar as usize;
+        let len = planes.count_ones() as usize * height * bytes_per_row;
+        self.check_memory(ar, len)?;
+        hooks.memory_access(ar, len, Access::Sprite);
+
+        self.v[0xF] = 0;
+        for plane in [0x1, 0x2].iter().filter(|p| planes & *p != 0) {
+            for row in 0..height {
+                // The sprite row starting at the most significant bit
+                let sprite_data = if bytes_per_row == 2 {
+                    self.read_u16(ar)
+                } else {
+                    (self.ram[ar] as u16) << 8
+                };
+                ar += bytes_per_row;
+
+                if self.display.draw_row(x, y + row, sprite_data, *plane, clip) {
+                    self.v[0xF] = 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Whether the emulated platform understands the opcodes of the given set
+    fn supports(&self, instruction_set: InstructionSet) -> bool {
+        self.platform.instruction_set >= instruction_set
+    }
+
+    // Skips the next instruction, which is four bytes long in case of F000 NNNN
+    fn skip(&mut self) {
+        let long_load = self.pc + 2 <= self.ram.len() && self.read_u16(self.pc) == 0xF000;
+        if self.supports(InstructionSet::XoChip) && long_load {
+            self.pc += 4;
+        } else {
+            self.pc += 2;
+        }
+    }
+
+    // Fails if the len bytes starting at addr are not all inside the memory
+    fn check_memory(&self, addr: usize, len: usize) -> Result<(), Fault> {
+        if addr + len > self.ram.len() {
+            return Err(Fault::MemoryOutOfBounds {
+                addr: addr.max(self.ram.len()),
+            });
+        }
+        Ok(())
+    }
+
+    // Number of RPL user flags available to FX75/FX85
+    fn flag_count(&self) -> usize {
+        if self.supports(InstructionSet::XoChip) {
+            16
+        } else if self.supports(InstructionSet::SChip10) {
+            8
+        } else {
+            0
+        }
+    }
+
+    // Big endian word at the given address
+    fn read_u16(&self, addr: usize) -> u16 {
+        ((self.ram[addr] as u16) << 8) | (self.ram[addr + 1] as u16)
+    }
+
+    // Registers X to Y of 5XY2/5XY3, which are walked backwards if X > Y
+    fn register_range(x: u8, y: u8) -> Box<dyn Iterator<Item = usize>> {
+        let (x, y) = (x as usize, y as usize);
+        if x <= y {
+            Box::new(x..=y)
+        } else {
+            Box::new((y..=x).rev())
+        }
+    }
+
+    // Ends a 60 Hz frame: counts down the timers and releases a DXYN stalled by the display wait quirk
+    fn tick(&mut self) {
+        self.dt = self.dt.saturating_sub(1);
+        self.st = self.st.saturating_sub(1);
+        self.vblank = true;
+        self.frame_cycles = 0;
+    }
+
+    // Operand of 8XY6/8XYE, depending on the shift quirk
+    fn shift_source(&self, x: u8, y: u8) -> u16 {
+        if self.platform.quirks.shift_uses_vy {
+            self.v[y as usize]
+        } else {
+            self.v[x as usize]
+        }
+    }
+
+    // Helper getters and setters for the frontends
+    pub fn platform(&self) -> &Platform {
+        &self.platform
+    }
+
+    pub fn set_key(&mut self, key: usize, status: bool) {
+        if key < 0x10 {
+            self.key_pad[key] = status;
+        }
+    }
+
+    // Loads the RPL flags from the storage and saves them back there whenever FX75 changes them
+    pub fn set_flag_storage(&mut self, mut storage: Box<dyn FlagStorage>) {
+        self.r = storage.load();
+        self.flag_storage = Some(storage);
+    }
+
+    // State of all 16 keys, bit N set while key N is held
+    pub fn key_pad(&self) -> u16 {
+        (0..16).fold(0, |bits, key| bits | ((self.key_pad[key] as u16) << key))
+    }
+
+    pub fn set_key_pad(&mut self, keys: u16) {
+        for (key, pressed) in self.key_pad.iter_mut().enumerate() {
+            *pressed = keys & (1 << key) != 0;
+        }
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn audio(&self) -> &Audio {
+        &self.audio
+    }
+
+    pub fn dt(&self) -> u8 {
+        self.dt
+    }
+
+    pub fn st(&self) -> u8 {
+        self.st
+    }
+
+    // Machine state for debuggers, values are only checked to be in range of their type
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+    }
+
+    pub fn i(&self) -> u16 {
+        self.ar
+    }
+
+    pub fn set_i(&mut self, ar: u16) {
+        self.ar = ar;
+    }
+
+    pub fn v(&self, x: usize) -> u8 {
+        self.v[x] as u8
+    }
+
+    pub fn set_v(&mut self, x: usize, value: u8) {
+        self.v[x] = value as u16;
+    }
+
+    pub fn set_dt(&mut self, dt: u8) {
+        self.dt = dt;
+    }
+
+    pub fn set_st(&mut self, st: u8) {
+        self.st = st;
+    }
+
+    // Number of return addresses on the stack
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    // Return addresses, the innermost call last
+    pub fn stack(&self) -> &[usize] {
+        &self.stack[..self.sp]
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.ram
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    // Serializes the complete machine state, including the platform settings and the RNG state
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut writer = Writer::new(MAGIC, SNAPSHOT_VERSION);
+        self.platform.save(&mut writer);
+        writer.u32(self.pc as u32);
+        writer.u16(self.ar);
+        writer.u32(self.sp as u32);
+        writer.bytes(&self.r);
+        for &v in self.v.iter() {
+            writer.u16(v);
+        }
+        writer.u8(self.dt);
+        writer.u8(self.st);
+        for &addr in self.stack.iter() {
+            writer.u32(addr as u32);
+        }
+        writer.bytes(&self.ram);
+        self.display.save(&mut writer);
+        self.audio.save(&mut writer);
+        writer.u16(self.key_pad());
+        writer.bool(self.vblank);
+        writer.u32(self.frame_cycles as u32);
+        writer.u64(self.random.state());
+        writer.finish()
+    }
+
+    // SHA-1 of the snapshot, two machines with the same hash will behave the same from now on
+    pub fn state_hash(&self) -> [u8; 20] {
+        sha1_smol::Sha1::from(self.snapshot()).digest().bytes()
+    }
+
+    // Restores a state written by SChip8::snapshot.
+    // The snapshot is validated completely first, on an error the machine is left untouched.
+    pub fn restore(&mut self, snapshot: &[u8]) -> Result<(), SnapshotError> {
+        let mut reader = Reader::new(snapshot, MAGIC, SNAPSHOT_VERSION)?;
+        let platform = Platform::load(&mut reader)?;
+        let pc = reader.u32()? as usize;
+        let ar = reader.u16()?;
+        let sp = reader.u32()? as usize;
+        let mut r = [0; 16];
+        r.copy_from_slice(reader.bytes(16)?);
+        let mut v = [0; 16];
+        for v in v.iter_mut() {
+            *v = reader.u16()?;
+            // Held in 16 bits, but the instructions expect them to fit into a byte
+            if *v > 0xFF {
+                return Err(SnapshotError::Invalid("register"));
+            }
+        }
+        let dt = reader.u8()?;
+        let st = reader.u8()?;
+        if sp > platform.stack_depth {
+            return Err(SnapshotError::Invalid("stack pointer"));
+        }
+        let mut stack = Vec::new();
+        for _ in 0..platform.stack_depth {
+            stack.push(reader.u32()? as usize);
+        }
+        let ram = reader.bytes(platform.memory_size)?.to_vec();
+        let display = Display::load(&mut reader)?;
+        let audio = Audio::load(&mut reader)?;
+        let key_pad = reader.u16()?;
+        let vblank = reader.bool()?;
+        let frame_cycles = reader.u32()? as usize;
+        let random = reader.u64()?;
+        reader.finish()?;
+
+        self.platform = platform;
+        self.pc = pc;
+        self.ar = ar;
+        self.sp = sp;
+        self.r = r;
+        self.v = v;
+        self.dt = dt;
+        self.st = st;
+        self.stack = stack;
+        self.ram = ram;
+        self.display = display;
+        self.audio = audio;
+        self.set_key_pad(key_pad);
+        self.vblank = vblank;
+        self.frame_cycles = frame_cycles;
+        self.random.set_state(random);
+        Ok(())
+    }
+}
+
+// Outcome of SChip8::run_frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub redraw: bool,  // The display changed
+    pub sound: bool,   // The sound timer is active
+    pub exited: bool,  // The program exited, the frame was cut short
+    pub stopped: bool, // The hooks stopped the frame, see SChip8::run_frame_with
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::SeededRandom;
+
+    // Draws random digits from a subroutine
+    const PROGRAM: [u8; 12] = [
+        0x22, 0x04, // 200: CALL #204
+        0x12, 0x00, // 202: JP #200
+        0xC0, 0x0F, // 204: RND V0, #0F
+        0xF0, 0x29, // 206: LD F, V0
+        0xD1, 0x25, // 208: DRW V1, V2, 5
+        0x00, 0xEE, // 20A: RET
+    ];
+
+    fn machine(platform: &Platform, seed: u64) -> SChip8 {
+        let rom = Rom::new(PROGRAM.to_vec()).unwrap();
+        SChip8::new(&rom, platform, Box::new(SeededRandom::new(seed))).unwrap()
+    }
+
+    // A machine in the middle of the program, with overridden settings
+    fn running_machine() -> SChip8 {
+        let mut platform = Platform::cosmac_vip();
+        platform.quirks.clip_sprites = !platform.quirks.clip_sprites;
+        platform.speed = 123;
+        let mut schip8 = machine(&platform, 42);
+        for _ in 0..20 {
+            schip8.run_frame(0x10).unwrap();
+        }
+        schip8.set_v(1, 0x12);
+        schip8.set_i(0x345);
+        schip8.set_dt(7);
+        schip8.set_key(0xA, true);
+        schip8
+    }
+
+    // Offset of the stack depth and V0 in the snapshot of the machine
+    fn offsets(schip8: &SChip8) -> (usize, usize) {
+        let mut writer = Writer::new(MAGIC, SNAPSHOT_VERSION);
+        schip8.platform.save(&mut writer);
+        let platform_end = writer.finish().len();
+        // Followed by the memory size, load address and speed
+        let stack_depth = platform_end - 16;
+        // Behind PC, I, SP and the RPL flags
+        let v0 = platform_end + 4 + 2 + 4 + 16;
+        (stack_depth, v0)
+    }
+
+    #[test]
+    fn restores_the_whole_state() {
+        let original = running_machine();
+        let snapshot = original.snapshot();
+
+        let mut restored = machine(&Platform::default(), 1);
+        restored.restore(&snapshot).unwrap();
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(restored.platform, original.platform);
+
+        // The random numbers continue the same way as well
+        let mut original = original;
+        for _ in 0..20 {
+            original.run_frame(0x10).unwrap();
+            restored.run_frame(0x10).unwrap();
+        }
+        assert_eq!(restored.state_hash(), original.state_hash());
+    }
+
+    #[test]
+    fn rejects_other_data() {
+        let snapshot = running_machine().snapshot();
+        let mut schip8 = machine(&Platform::default(), 1);
+        let untouched = schip8.snapshot();
+
+        let mut newer = snapshot.clone();
+        newer[4..6].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            schip8.restore(&newer),
+            Err(SnapshotError::UnsupportedVersion {
+                version: SNAPSHOT_VERSION + 1
+            })
+        );
+
+        let mut other = snapshot.clone();
+        other[0] = b'X';
+        assert_eq!(schip8.restore(&other), Err(SnapshotError::NotASnapshot));
+        assert_eq!(schip8.restore(&[]), Err(SnapshotError::NotASnapshot));
+
+        for len in (6..snapshot.len())
+            .step_by(97)
+            .chain(Some(snapshot.len() - 1))
+        {
+            assert_eq!(
+                schip8.restore(&snapshot[..len]),
+                Err(SnapshotError::Truncated),
+                "{} bytes",
+                len
+            );
+        }
+        let mut longer = snapshot;
+        longer.push(0);
+        assert!(schip8.restore(&longer).is_err());
+
+        assert_eq!(schip8.snapshot(), untouched);
+    }
+
+    #[test]
+    fn rejects_values_out_of_range() {
+        let original = running_machine();
+        let snapshot = original.snapshot();
+        let (stack_depth, v0) = offsets(&original);
+        let mut schip8 = machine(&Platform::default(), 1);
+        let untouched = schip8.snapshot();
+
+        let mut register = snapshot.clone();
+        register[v0 + 2 * 5..v0 + 2 * 5 + 2].copy_from_slice(&0x100u16.to_le_bytes());
+        assert_eq!(
+            schip8.restore(&register),
+            Err(SnapshotError::Invalid("register"))
+        );
+
+        for &depth in [0u32, 100_000].iter() {
+            let mut stack = snapshot.clone();
+            stack[stack_depth..stack_depth + 4].copy_from_slice(&depth.to_le_bytes());
+            assert_eq!(
+                schip8.restore(&stack),
+                Err(SnapshotError::Invalid("stack depth"))
+            );
+        }
+
+        let mut memory = snapshot;
+        memory[stack_depth + 4..stack_depth + 8].copy_from_slice(&0x100u32.to_le_bytes());
+        assert_eq!(
+            schip8.restore(&memory),
+            Err(SnapshotError::Invalid("memory size"))
+        );
+
+        assert_eq!(schip8.snapshot(), untouched);
+    }
+}
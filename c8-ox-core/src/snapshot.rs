@@ -0,0 +1,153 @@
+use std::{error, fmt};
+
+// Version of the snapshot format written by SChip8::snapshot.
+// Bump it whenever the layout changes, older versions are then rejected with UnsupportedVersion.
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+// Every snapshot starts with these bytes, followed by the version
+pub(crate) const MAGIC: &[u8; 4] = b"C8OX";
+
+// Why a snapshot could not be restored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    // The data does not start with the snapshot magic
+    NotASnapshot,
+    // The snapshot was written by an incompatible version of the emulator
+    UnsupportedVersion { version: u16 },
+    // The data ends in the middle of the snapshot
+    Truncated,
+    // A value in the snapshot is out of range
+    Invalid(&'static str),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::NotASnapshot => write!(f, "not a snapshot"),
+            SnapshotError::UnsupportedVersion { version } => write!(
+                f,
+                "snapshot version {} is not supported, expected version {}",
+                version, SNAPSHOT_VERSION
+            ),
+            SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+            SnapshotError::Invalid(what) => write!(f, "snapshot contains an invalid {}", what),
+        }
+    }
+}
+
+impl error::Error for SnapshotError {}
+
+// Appends values in little endian to a snapshot, or another file in the same style like a movie
+pub(crate) struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    // Starts the data with the magic and the version of the format
+    pub(crate) fn new(magic: &[u8; 4], version: u16) -> Writer {
+        let mut writer = Writer { data: Vec::new() };
+        writer.bytes(magic);
+        writer.u16(version);
+        writer
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+// Reads the values appended by a Writer back in the same order
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    // Checks the magic and the version
+    pub(crate) fn new(
+        data: &'a [u8],
+        magic: &[u8; 4],
+        version: u16,
+    ) -> Result<Reader<'a>, SnapshotError> {
+        let mut reader = Reader { data };
+        if reader.bytes(magic.len()).ok() != Some(&magic[..]) {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        let found = reader.u16()?;
+        if found != version {
+            return Err(SnapshotError::UnsupportedVersion { version: found });
+        }
+        Ok(reader)
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.data.len() < len {
+            return Err(SnapshotError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, SnapshotError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SnapshotError::Invalid("flag")),
+        }
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, SnapshotError> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.bytes(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, SnapshotError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, SnapshotError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    // Fails if anything is left, so a corrupted length cannot go unnoticed
+    pub(crate) fn finish(self) -> Result<(), SnapshotError> {
+        if self.data.is_empty() {
+            Ok(())
+        } else {
+            Err(SnapshotError::Invalid("length"))
+        }
+    }
+}
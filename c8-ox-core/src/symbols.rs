@@ -0,0 +1,174 @@
+use crate::flow::ControlFlow;
+use std::{error, fmt, path::Path};
+
+// A line of a symbol file that could not be parsed, starting at 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SymbolError {
+    pub line: usize,
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid symbol file entry in line {}", self.line)
+    }
+}
+
+impl error::Error for SymbolError {}
+
+// Source line an instruction or data was assembled from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLine {
+    pub addr: usize,
+    pub file: String,
+    pub line: usize, // Starting at 1
+}
+
+// Names of addresses and the source lines they were assembled from, written by c8-ox-asm --symbols.
+// The file has one entry per line, addresses are hexadecimal and lines starting with ; are comments:
+//   label 200 start
+//   line 200 12 brix.asm
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    labels: Vec<(usize, String)>, // Sorted by address
+    lines: Vec<SourceLine>,       // Sorted by address
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    pub fn parse(text: &str) -> Result<SymbolTable, SymbolError> {
+        let mut symbols = SymbolTable::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let error = SymbolError { line: i + 1 };
+            let mut fields = line.splitn(4, char::is_whitespace);
+            let kind = fields.next().ok_or(error)?;
+            let addr = fields
+                .next()
+                .and_then(|addr| usize::from_str_radix(addr, 16).ok())
+                .ok_or(error)?;
+            match kind {
+                "label" => {
+                    let name = fields.next().ok_or(error)?;
+                    if fields.next().is_some() {
+                        return Err(error);
+                    }
+                    symbols.add_label(addr, name);
+                }
+                "line" => {
+                    let number = fields
+                        .next()
+                        .and_then(|number| number.parse().ok())
+                        .ok_or(error)?;
+                    let file = fields.next().map(str::trim).ok_or(error)?;
+                    symbols.add_line(addr, file, number);
+                }
+                _ => return Err(error),
+            }
+        }
+        Ok(symbols)
+    }
+
+    // Labels like sub_2A4 for the subroutines a program calls, for programs without a symbol file
+    pub fn generate(program: &[u8], load_address: usize) -> SymbolTable {
+        let mut symbols = SymbolTable::new();
+        let flow = ControlFlow::analyze(program, load_address);
+        for function in flow.functions().iter().filter(|f| f.entry != load_address) {
+            symbols.add_label(function.entry, &format!("sub_{:03X}", function.entry));
+        }
+        symbols
+    }
+
+    pub fn add_label(&mut self, addr: usize, name: &str) {
+        let index = self.labels.partition_point(|(a, _)| *a <= addr);
+        self.labels.insert(index, (addr, name.to_string()));
+    }
+
+    pub fn add_line(&mut self, addr: usize, file: &str, line: usize) {
+        let index = self.lines.partition_point(|entry| entry.addr <= addr);
+        self.lines.insert(
+            index,
+            SourceLine {
+                addr,
+                file: file.to_string(),
+                line,
+            },
+        );
+    }
+
+    // Labels sorted by address
+    pub fn labels(&self) -> impl Iterator<Item = (usize, &str)> + '_ {
+        self.labels
+            .iter()
+            .map(|(addr, name)| (*addr, name.as_str()))
+    }
+
+    // The first label at the address
+    pub fn label_at(&self, addr: usize) -> Option<&str> {
+        let index = self.labels.partition_point(|(a, _)| *a < addr);
+        match self.labels.get(index) {
+            Some((a, name)) if *a == addr => Some(name),
+            _ => None,
+        }
+    }
+
+    // The last label at or before the address, together with its address
+    pub fn label_before(&self, addr: usize) -> Option<(usize, &str)> {
+        let index = self.labels.partition_point(|(a, _)| *a <= addr);
+        let (addr, name) = self.labels.get(index.checked_sub(1)?)?;
+        Some((*addr, name))
+    }
+
+    // The address as the last label at or before it, like main-loop or main-loop+4
+    pub fn describe(&self, addr: usize) -> Option<String> {
+        match self.label_before(addr)? {
+            (start, label) if start == addr => Some(label.to_string()),
+            (start, label) => Some(format!("{}+{:X}", label, addr - start)),
+        }
+    }
+
+    pub fn addr_of(&self, name: &str) -> Option<usize> {
+        self.labels
+            .iter()
+            .find(|(_, label)| label == name)
+            .map(|(addr, _)| *addr)
+    }
+
+    // Source lines sorted by address
+    pub fn lines(&self) -> &[SourceLine] {
+        &self.lines
+    }
+
+    // The source line the byte at the address was assembled from
+    pub fn line_at(&self, addr: usize) -> Option<&SourceLine> {
+        let index = self.lines.partition_point(|entry| entry.addr < addr);
+        self.lines.get(index).filter(|entry| entry.addr == addr)
+    }
+
+    // Address of the first line in the file at or after the given one, together with that line.
+    // Files match if they are equal or the path ends with the one in the table.
+    pub fn addr_of_line(&self, file: &Path, line: usize) -> Option<(usize, usize)> {
+        self.lines
+            .iter()
+            .filter(|entry| entry.line >= line && file.ends_with(&entry.file))
+            .min_by_key(|entry| (entry.line, entry.addr))
+            .map(|entry| (entry.addr, entry.line))
+    }
+
+    // The file format read by SymbolTable::parse
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (addr, name) in self.labels.iter() {
+            text += &format!("label {:03X} {}\n", addr, name);
+        }
+        for entry in self.lines.iter() {
+            text += &format!("line {:03X} {} {}\n", entry.addr, entry.line, entry.file);
+        }
+        text
+    }
+}
@@ -0,0 +1,107 @@
+use crate::hooks::Hooks;
+use crate::instruction::{decode_at, Syntax};
+use crate::schip8::SChip8;
+use crate::symbols::SymbolTable;
+use std::io::{self, Write};
+
+// Writes a line per executed instruction with the state of the machine before it, e.g.
+//   PC:0200 OP:6000 LD V0, #00          V0:00 V1:00 ... VF:00 I:0000 SP:00 DT:00 ST:00
+// Every value is written as NAME:HEX, so logs of other emulators in the same style can be compared
+// with c8-ox-tracediff. Run the machine with SChip8::run_frame_with(key, &mut tracer).
+pub struct Tracer<W: Write> {
+    output: W,
+    range: Option<(usize, usize)>, // Addresses of the instructions to log, inclusive
+    frames: Option<(u64, u64)>,    // Frames to log, inclusive and counted from 0
+    frame: u64,                    // Number of the running frame
+    symbols: SymbolTable,          // Names of the addresses in the instructions
+    error: Option<io::Error>,      // The first error, nothing is written after it
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(output: W) -> Tracer<W> {
+        Tracer {
+            output,
+            range: None,
+            frames: None,
+            frame: 0,
+            symbols: SymbolTable::new(),
+            error: None,
+        }
+    }
+
+    // Logs only the instructions from start to end, inclusive
+    pub fn set_range(&mut self, start: usize, end: usize) {
+        self.range = Some((start, end));
+    }
+
+    // Logs only during the frames from first to last, inclusive
+    pub fn set_frames(&mut self, first: u64, last: u64) {
+        self.frames = Some((first, last));
+    }
+
+    // Writes the addresses in the instructions as the labels of the table, like CALL draw-score
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = symbols;
+    }
+
+    // Flushes the output, returning it or the first error writing to it
+    pub fn finish(mut self) -> Result<W, io::Error> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.output.flush()?;
+        Ok(self.output)
+    }
+
+    fn logged(&self, pc: usize) -> bool {
+        let in_range = self
+            .range
+            .is_none_or(|(start, end)| (start..=end).contains(&pc));
+        let in_frames = self
+            .frames
+            .is_none_or(|(first, last)| (first..=last).contains(&self.frame));
+        self.error.is_none() && in_range && in_frames
+    }
+}
+
+// The line of the instruction at the program counter, without line break
+fn trace_line(schip8: &SChip8, symbols: &SymbolTable) -> String {
+    let pc = schip8.pc();
+    let memory = schip8.memory();
+    let (opcode, instruction) = match decode_at(memory, pc) {
+        Some(instruction) => (
+            format!("{:02X}{:02X}", memory[pc], memory[pc + 1]),
+            instruction.format(Syntax::Mnemonic, &|addr| {
+                symbols.label_at(addr as usize).map(str::to_string)
+            }),
+        ),
+        None => ("????".to_string(), String::new()),
+    };
+    let mut line = format!("PC:{:04X} OP:{} {:<20}", pc, opcode, instruction);
+    for x in 0..16 {
+        line += &format!(" V{:X}:{:02X}", x, schip8.v(x));
+    }
+    line += &format!(
+        " I:{:04X} SP:{:02X} DT:{:02X} ST:{:02X}",
+        schip8.i(),
+        schip8.sp(),
+        schip8.dt(),
+        schip8.st()
+    );
+    line
+}
+
+impl<W: Write> Hooks for Tracer<W> {
+    fn before_instruction(&mut self, schip8: &SChip8) -> bool {
+        if self.logged(schip8.pc()) {
+            if let Err(error) = writeln!(self.output, "{}", trace_line(schip8, &self.symbols)) {
+                self.error = Some(error);
+            }
+        }
+        true
+    }
+
+    fn frame_finished(&mut self, _schip8: &SChip8) {
+        self.frame += 1;
+    }
+}
@@ -0,0 +1,685 @@
+use c8_ox_core::{
+    decode_at, Condition, Debugger, Platform, Rom, SChip8, SeededRandom, Stop, SymbolTable, Syntax,
+};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The machine is the only thread
+const THREAD_ID: u64 = 1;
+// variablesReference of the register scope, 0 means a variable has no children
+const REGISTERS_REFERENCE: u64 = 1;
+// No key is pressed, there is no keyboard
+const NO_KEY: usize = 0x10;
+
+// Registers shown as variables, with the number of hex digits of their values
+const REGISTERS: [(&str, usize); 21] = [
+    ("V0", 2),
+    ("V1", 2),
+    ("V2", 2),
+    ("V3", 2),
+    ("V4", 2),
+    ("V5", 2),
+    ("V6", 2),
+    ("V7", 2),
+    ("V8", 2),
+    ("V9", 2),
+    ("VA", 2),
+    ("VB", 2),
+    ("VC", 2),
+    ("VD", 2),
+    ("VE", 2),
+    ("VF", 2),
+    ("I", 4),
+    ("PC", 3),
+    ("SP", 2),
+    ("DT", 2),
+    ("ST", 2),
+];
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Body of the response to a request, or the message of an error
+type Reply = Result<Value, String>;
+
+// Breakpoint at an address, set by source line or on an instruction
+type Breakpoint = (usize, Option<Condition>);
+
+// Serves the Debug Adapter Protocol for a single session: the client launches a ROM, sets
+// breakpoints and then runs it. The machine is driven by main, which calls run_frame at 60 Hz.
+pub struct Adapter {
+    seq: u64,           // Sequence number of the last message sent
+    events: Vec<Value>, // Sent after the response to the request being handled
+    debugger: Debugger,
+    schip8: Option<SChip8>, // Created by launch
+    symbols: SymbolTable,
+    // Breakpoints by source file and on instructions, both are merged into the debugger
+    source_breakpoints: BTreeMap<PathBuf, Vec<Breakpoint>>,
+    instruction_breakpoints: Vec<Breakpoint>,
+    stop_on_entry: bool,
+    configured: bool, // The program starts once the client set its breakpoints
+    exited: bool,
+    finished: bool, // The client disconnected
+}
+
+impl Adapter {
+    pub fn new() -> Adapter {
+        Adapter {
+            seq: 0,
+            events: Vec::new(),
+            debugger: Debugger::new(),
+            schip8: None,
+            symbols: SymbolTable::new(),
+            source_breakpoints: BTreeMap::new(),
+            instruction_breakpoints: Vec::new(),
+            stop_on_entry: false,
+            configured: false,
+            exited: false,
+            finished: false,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    // Whether main should run frames
+    pub fn running(&self) -> bool {
+        self.schip8.is_some() && self.configured && !self.exited && !self.debugger.paused()
+    }
+
+    pub fn run_frame(&mut self) {
+        let schip8 = match &mut self.schip8 {
+            Some(schip8) => schip8,
+            None => return,
+        };
+        match schip8.run_frame_with(NO_KEY, &mut self.debugger) {
+            Ok(frame) if frame.exited => {
+                self.exited = true;
+                self.event("exited", json!({ "exitCode": 0 }));
+                self.event("terminated", json!({}));
+            }
+            Ok(frame) if frame.stopped => {
+                let reason = match self.debugger.stop() {
+                    Stop::Pause => "pause",
+                    Stop::Step => "step",
+                    Stop::Breakpoint => "breakpoint",
+                    Stop::Watchpoint { .. } => "data breakpoint",
+                };
+                self.stopped(reason, None);
+            }
+            Ok(_) => {}
+            // The machine is left as it was before the faulting instruction
+            Err(error) => {
+                self.debugger.pause();
+                self.output(format!("The ROM crashed: {}\n", error));
+                self.stopped("exception", Some(error.to_string()));
+            }
+        }
+        self.flush_events();
+    }
+
+    // Answers a request, other messages are ignored
+    pub fn handle(&mut self, message: &Value) {
+        if message["type"] != "request" {
+            return;
+        }
+        let command = message["command"].as_str().unwrap_or("");
+        let args = &message["arguments"];
+        let reply = match command {
+            "initialize" => Ok(capabilities()),
+            "launch" => self.launch(args),
+            "setBreakpoints" => self.set_breakpoints(args),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(args),
+            "configurationDone" => self.configuration_done(),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(json!({
+                "scopes": [{
+                    "name": "Registers",
+                    "presentationHint": "registers",
+                    "variablesReference": REGISTERS_REFERENCE,
+                    "expensive": false,
+                }]
+            })),
+            "variables" => self.variables(args),
+            "setVariable" => self.set_variable(args),
+            "continue" | "next" | "stepIn" | "stepOut" => self.resume(command),
+            "pause" => self.pause(),
+            "readMemory" => self.read_memory(args),
+            "writeMemory" => self.write_memory(args),
+            "disassemble" => self.disassemble(args),
+            "terminate" => {
+                self.exited = true;
+                self.event("terminated", json!({}));
+                Ok(Value::Null)
+            }
+            "disconnect" => {
+                self.finished = true;
+                Ok(Value::Null)
+            }
+            _ => Err(format!("{} is not supported", command)),
+        };
+
+        let mut response = json!({
+            "type": "response",
+            "request_seq": message["seq"],
+            "command": command,
+            "success": reply.is_ok(),
+        });
+        match reply {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(error) => response["message"] = Value::from(error),
+        }
+        self.send(response);
+        self.flush_events();
+    }
+
+    fn send(&mut self, mut message: Value) {
+        self.seq += 1;
+        message["seq"] = Value::from(self.seq);
+        crate::write_message(&message);
+    }
+
+    fn event(&mut self, event: &str, body: Value) {
+        self.events
+            .push(json!({ "type": "event", "event": event, "body": body }));
+    }
+
+    fn flush_events(&mut self) {
+        for event in std::mem::take(&mut self.events) {
+            self.send(event);
+        }
+    }
+
+    fn output(&mut self, text: String) {
+        self.event("output", json!({ "category": "console", "output": text }));
+    }
+
+    fn stopped(&mut self, reason: &str, description: Option<String>) {
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(description) = description {
+            body["description"] = Value::from(description.clone());
+            body["text"] = Value::from(description);
+        }
+        self.event("stopped", body);
+    }
+
+    fn machine(&self) -> Result<&SChip8, String> {
+        self.schip8
+            .as_ref()
+            .ok_or_else(|| "No program was launched".to_string())
+    }
+
+    // Arguments: program (path of the ROM), platform (name), seed (number), symbols (path, the ROM
+    // with the extension .sym by default, generated if there is none) and stopOnEntry
+    fn launch(&mut self, args: &Value) -> Reply {
+        let program = args["program"]
+            .as_str()
+            .ok_or("launch expects the path of the ROM as program")?;
+        let platform = match args["platform"].as_str() {
+            Some(name) => Platform::from_name(name).ok_or_else(|| {
+                format!(
+                    "Unknown platform {}, available are: {}",
+                    name,
+                    Platform::NAMES.join(", ")
+                )
+            })?,
+            None => Platform::default(),
+        };
+        let seed = args["seed"].as_u64().unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_nanos() as u64)
+        });
+        let rom =
+            Rom::read(program).map_err(|error| format!("Unable to open {}: {}", program, error))?;
+        let schip8 = SChip8::new(&rom, &platform, Box::new(SeededRandom::new(seed)))
+            .map_err(|error| format!("Unable to load {}: {}", program, error))?;
+
+        let symbols = match args["symbols"].as_str() {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(Path::new(program).with_extension("sym")).filter(|path| path.exists()),
+        };
+        if let Some(path) = symbols {
+            let text = fs::read_to_string(&path)
+                .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+            self.symbols = SymbolTable::parse(&text)
+                .map_err(|error| format!("{}: {}", path.display(), error))?;
+            self.output(format!("Loaded symbols from {}\n", path.display()));
+        } else {
+            self.symbols = SymbolTable::generate(rom.data(), platform.load_address);
+        }
+
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        // Printed, so a run can be reproduced with the seed
+        self.output(format!(
+            "Emulating platform {}, seed {}\n",
+            platform.name, seed
+        ));
+        self.schip8 = Some(schip8);
+        // Breakpoints by line can only be resolved now that the symbols are known
+        self.event("initialized", json!({}));
+        Ok(Value::Null)
+    }
+
+    fn configuration_done(&mut self) -> Reply {
+        self.configured = true;
+        let schip8 = self.machine()?;
+        // The debugger would run the first instruction even with a breakpoint on it
+        let pc = schip8.pc();
+        let breakpoint = self
+            .debugger
+            .breakpoints()
+            .any(|(addr, condition)| addr == pc && condition.is_none_or(|c| c.holds(schip8)));
+        if self.stop_on_entry {
+            self.stopped("entry", None);
+        } else if breakpoint {
+            self.stopped("breakpoint", None);
+        } else {
+            self.debugger.resume();
+        }
+        Ok(Value::Null)
+    }
+
+    // Replaces the breakpoints in a source file, each moved to the next line with code
+    fn set_breakpoints(&mut self, args: &Value) -> Reply {
+        let path = args["source"]["path"]
+            .as_str()
+            .ok_or("setBreakpoints expects the path of the source")?;
+        let path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        let mut breakpoints = Vec::new();
+        let mut results = Vec::new();
+        for requested in list(&args["breakpoints"]) {
+            let line = requested["line"].as_u64().unwrap_or(0) as usize;
+            let result = match (
+                parse_condition(&requested["condition"]),
+                self.symbols.addr_of_line(&path, line),
+            ) {
+                (Err(error), _) => json!({ "verified": false, "message": error }),
+                (_, None) => json!({
+                    "verified": false,
+                    "line": line,
+                    "message": "No code at or after this line",
+                }),
+                (Ok(condition), Some((addr, line))) => {
+                    breakpoints.push((addr, condition));
+                    json!({
+                        "verified": true,
+                        "line": line,
+                        "instructionReference": reference(addr as i64),
+                    })
+                }
+            };
+            results.push(result);
+        }
+        self.source_breakpoints.insert(path, breakpoints);
+        self.update_breakpoints();
+        Ok(json!({ "breakpoints": results }))
+    }
+
+    // Replaces the breakpoints set on addresses, like in a disassembly view
+    fn set_instruction_breakpoints(&mut self, args: &Value) -> Reply {
+        let mut breakpoints = Vec::new();
+        let mut results = Vec::new();
+        for requested in list(&args["breakpoints"]) {
+            let addr = parse_reference(requested["instructionReference"].as_str().unwrap_or(""))
+                .and_then(|addr| addr.checked_add(requested["offset"].as_i64().unwrap_or(0)))
+                .filter(|&addr| addr >= 0);
+            let result = match (parse_condition(&requested["condition"]), addr) {
+                (Err(error), _) => json!({ "verified": false, "message": error }),
+                (_, None) => json!({ "verified": false, "message": "Invalid address" }),
+                (Ok(condition), Some(addr)) => {
+                    breakpoints.push((addr as usize, condition));
+                    json!({ "verified": true, "instructionReference": reference(addr) })
+                }
+            };
+            results.push(result);
+        }
+        self.instruction_breakpoints = breakpoints;
+        self.update_breakpoints();
+        Ok(json!({ "breakpoints": results }))
+    }
+
+    fn update_breakpoints(&mut self) {
+        let old: Vec<usize> = self.debugger.breakpoints().map(|(addr, _)| addr).collect();
+        for addr in old {
+            self.debugger.remove_breakpoint(addr);
+        }
+        let breakpoints = self
+            .source_breakpoints
+            .values()
+            .flatten()
+            .chain(self.instruction_breakpoints.iter());
+        for (addr, condition) in breakpoints {
+            self.debugger.add_breakpoint(*addr, condition.clone());
+        }
+    }
+
+    // The current instruction, followed by the calls of the subroutines it is in
+    fn stack_trace(&self) -> Reply {
+        let schip8 = self.machine()?;
+        let mut addrs = vec![schip8.pc()];
+        // The stack holds return addresses, the calls are right before them
+        addrs.extend(
+            schip8
+                .stack()
+                .iter()
+                .rev()
+                .map(|addr| addr.saturating_sub(2)),
+        );
+        let frames: Vec<Value> = addrs
+            .iter()
+            .enumerate()
+            .map(|(id, &addr)| self.stack_frame(id, addr))
+            .collect();
+        Ok(json!({ "totalFrames": frames.len(), "stackFrames": frames }))
+    }
+
+    fn stack_frame(&self, id: usize, addr: usize) -> Value {
+        let name = self
+            .symbols
+            .describe(addr)
+            .unwrap_or_else(|| format!("{:03X}", addr));
+        let mut frame = json!({
+            "id": id,
+            "name": name,
+            "line": 0,
+            "column": 0,
+            "instructionPointerReference": reference(addr as i64),
+        });
+        if let Some(line) = self.symbols.line_at(addr) {
+            frame["source"] = source(&line.file);
+            frame["line"] = Value::from(line.line);
+            frame["column"] = Value::from(1);
+        }
+        frame
+    }
+
+    fn variables(&self, args: &Value) -> Reply {
+        let schip8 = self.machine()?;
+        if args["variablesReference"] != REGISTERS_REFERENCE {
+            return Ok(json!({ "variables": [] }));
+        }
+        let variables: Vec<Value> = REGISTERS
+            .iter()
+            .enumerate()
+            .map(|(n, &(name, digits))| {
+                let value = register(schip8, n);
+                let mut variable = json!({
+                    "name": name,
+                    "value": format!("0x{:01$X}", value, digits),
+                    "variablesReference": 0,
+                });
+                // Addresses can be opened in a memory view
+                if name == "I" || name == "PC" {
+                    variable["memoryReference"] = Value::from(reference(value as i64));
+                }
+                variable
+            })
+            .collect();
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn set_variable(&mut self, args: &Value) -> Reply {
+        let schip8 = self.schip8.as_mut().ok_or("No program was launched")?;
+        let name = args["name"].as_str().unwrap_or("");
+        let n = REGISTERS
+            .iter()
+            .position(|&(register, _)| register == name)
+            .ok_or_else(|| format!("Unknown register {}", name))?;
+        let text = args["value"].as_str().unwrap_or("");
+        let value = parse_number(text).ok_or_else(|| format!("Invalid number {}", text))?;
+        let max = match name {
+            "I" => 0xFFFF,
+            "PC" => schip8.memory().len() - 2,
+            "SP" => return Err("The stack pointer cannot be changed".to_string()),
+            _ => 0xFF,
+        };
+        if value > max {
+            return Err(format!(
+                "{} is out of range, the maximum is 0x{:X}",
+                text, max
+            ));
+        }
+        set_register(schip8, n, value);
+        Ok(json!({ "value": format!("0x{:01$X}", value, REGISTERS[n].1) }))
+    }
+
+    fn resume(&mut self, command: &str) -> Reply {
+        let schip8 = self.schip8.as_ref().ok_or("No program was launched")?;
+        match command {
+            "next" => self.debugger.step_over(schip8),
+            "stepIn" => self.debugger.step(),
+            "stepOut" => {
+                if !self.debugger.step_out(schip8) {
+                    return Err("The program is not in a subroutine".to_string());
+                }
+            }
+            _ => self.debugger.resume(),
+        }
+        Ok(json!({ "allThreadsContinued": true }))
+    }
+
+    fn pause(&mut self) -> Reply {
+        if !self.debugger.paused() {
+            self.debugger.pause();
+            self.stopped("pause", None);
+        }
+        Ok(Value::Null)
+    }
+
+    fn read_memory(&self, args: &Value) -> Reply {
+        let memory = self.machine()?.memory();
+        let addr = memory_address(args)?;
+        let count = args["count"].as_u64().unwrap_or(0) as usize;
+        let start = addr.min(memory.len());
+        let end = addr.saturating_add(count).min(memory.len());
+        Ok(json!({
+            "address": reference(addr as i64),
+            "data": to_base64(&memory[start..end]),
+            "unreadableBytes": count - (end - start),
+        }))
+    }
+
+    fn write_memory(&mut self, args: &Value) -> Reply {
+        let addr = memory_address(args)?;
+        let schip8 = self.schip8.as_mut().ok_or("No program was launched")?;
+        let data = from_base64(args["data"].as_str().unwrap_or("")).ok_or("Invalid data")?;
+        let memory = schip8.memory_mut();
+        if addr.saturating_add(data.len()) > memory.len() {
+            return Err("The data does not fit into the memory".to_string());
+        }
+        memory[addr..addr + data.len()].copy_from_slice(&data);
+        Ok(json!({ "bytesWritten": data.len() }))
+    }
+
+    // Instructions are decoded from the given address on, so instructionOffset counts 2 byte words
+    fn disassemble(&self, args: &Value) -> Reply {
+        let memory = self.machine()?.memory();
+        let start = memory_address(args)? as i64;
+        let mut addr = args["instructionOffset"]
+            .as_i64()
+            .unwrap_or(0)
+            .checked_mul(2)
+            .and_then(|offset| start.checked_add(offset))
+            .ok_or_else(|| format!("Invalid address {}", reference(start)))?;
+        let count = args["instructionCount"].as_u64().unwrap_or(0);
+        let label = |addr: u16| self.symbols.label_at(addr as usize).map(str::to_string);
+
+        let mut instructions = Vec::new();
+        for _ in 0..count {
+            let decoded = usize::try_from(addr)
+                .ok()
+                .and_then(|addr| Some((addr, decode_at(memory, addr)?)));
+            let (start, instruction) = match decoded {
+                Some(decoded) => decoded,
+                None => {
+                    instructions.push(json!({
+                        "address": reference(addr),
+                        "instruction": "",
+                        "presentationHint": "invalid",
+                    }));
+                    addr = addr.saturating_add(2);
+                    continue;
+                }
+            };
+            let bytes: Vec<String> = memory[start..start + instruction.size()]
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect();
+            let mut entry = json!({
+                "address": reference(addr),
+                "instructionBytes": bytes.join(" "),
+                "instruction": instruction.format(Syntax::Mnemonic, &label),
+            });
+            if let Some(symbol) = self.symbols.label_at(start) {
+                entry["symbol"] = Value::from(symbol);
+            }
+            if let Some(line) = self.symbols.line_at(start) {
+                entry["location"] = source(&line.file);
+                entry["line"] = Value::from(line.line);
+            }
+            instructions.push(entry);
+            addr = addr.saturating_add(instruction.size() as i64);
+        }
+        Ok(json!({ "instructions": instructions }))
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsConditionalBreakpoints": true,
+        "supportsInstructionBreakpoints": true,
+        "supportsSetVariable": true,
+        "supportsReadMemoryRequest": true,
+        "supportsWriteMemoryRequest": true,
+        "supportsDisassembleRequest": true,
+        "supportsTerminateRequest": true,
+    })
+}
+
+fn list(value: &Value) -> &[Value] {
+    value.as_array().map_or(&[], Vec::as_slice)
+}
+
+fn source(file: &str) -> Value {
+    let name = Path::new(file).file_name().map_or_else(
+        || file.to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    json!({ "name": name, "path": file })
+}
+
+fn parse_condition(condition: &Value) -> Result<Option<Condition>, String> {
+    match condition.as_str().map(str::trim) {
+        Some(text) if !text.is_empty() => Condition::parse(text)
+            .map(Some)
+            .ok_or_else(|| format!("Invalid condition {}", text)),
+        _ => Ok(None),
+    }
+}
+
+// Memory references are addresses like 0x2A4
+fn reference(addr: i64) -> String {
+    if addr < 0 {
+        format!("-0x{:X}", -addr)
+    } else {
+        format!("0x{:X}", addr)
+    }
+}
+
+fn parse_reference(text: &str) -> Option<i64> {
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))?;
+    i64::from_str_radix(digits, 16).ok()
+}
+
+// memoryReference plus offset of readMemory, writeMemory and disassemble
+fn memory_address(args: &Value) -> Result<usize, String> {
+    let text = args["memoryReference"].as_str().unwrap_or("");
+    let addr = parse_reference(text).ok_or_else(|| format!("Invalid memory reference {}", text))?;
+    let addr = addr
+        .checked_add(args["offset"].as_i64().unwrap_or(0))
+        .ok_or_else(|| format!("Invalid address {}", text))?;
+    usize::try_from(addr).map_err(|_| format!("Invalid address {}", reference(addr)))
+}
+
+// Hexadecimal, with or without 0x or # in front, like the numbers of breakpoint conditions
+fn parse_number(text: &str) -> Option<usize> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .or_else(|| text.strip_prefix('#'))
+        .unwrap_or(text);
+    usize::from_str_radix(digits, 16).ok()
+}
+
+fn register(schip8: &SChip8, n: usize) -> usize {
+    match n {
+        0..=15 => schip8.v(n) as usize,
+        16 => schip8.i() as usize,
+        17 => schip8.pc(),
+        18 => schip8.sp(),
+        19 => schip8.dt() as usize,
+        _ => schip8.st() as usize,
+    }
+}
+
+fn set_register(schip8: &mut SChip8, n: usize, value: usize) {
+    match n {
+        0..=15 => schip8.set_v(n, value as u8),
+        16 => schip8.set_i(value as u16),
+        17 => schip8.set_pc(value),
+        19 => schip8.set_dt(value as u8),
+        20 => schip8.set_st(value as u8),
+        _ => {}
+    }
+}
+
+fn to_base64(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+            bits | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64[(bits >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+fn from_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut bits = 0u32;
+    let mut len = 0;
+    for c in text
+        .bytes()
+        .filter(|&c| c != b'=' && !c.is_ascii_whitespace())
+    {
+        bits = bits << 6 | BASE64.iter().position(|&b| b == c)? as u32;
+        len += 6;
+        if len >= 8 {
+            len -= 8;
+            bytes.push((bits >> len) as u8);
+            bits &= (1 << len) - 1;
+        }
+    }
+    Some(bytes)
+}
@@ -0,0 +1,84 @@
+mod adapter;
+
+use adapter::Adapter;
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Length of a 60 Hz frame
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+// Reads the next message, framed by a Content-Length header like in the Language Server Protocol.
+// None at the end of the input or if it is not a message.
+fn read_message<R: BufRead>(input: &mut R) -> Option<Value> {
+    let mut len = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                len = value.trim().parse().ok();
+            }
+        }
+    }
+    let mut body = vec![0; len?];
+    input.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+fn write_message(message: &Value) {
+    let body = message.to_string();
+    let mut stdout = io::stdout().lock();
+    // Nothing to do if the client is gone, the input ends as well then
+    let _ = write!(stdout, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let _ = stdout.flush();
+}
+
+// Messages are read on their own thread, so the program runs while none arrive
+fn spawn_reader() -> Receiver<Value> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut input = io::stdin().lock();
+        while let Some(message) = read_message(&mut input) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+fn main() {
+    let messages = spawn_reader();
+    let mut adapter = Adapter::new();
+    let mut next_frame = Instant::now();
+
+    while !adapter.finished() {
+        // Paused programs wait for the next message, running ones for the next frame
+        let message = if adapter.running() {
+            let now = Instant::now();
+            if next_frame <= now {
+                adapter.run_frame();
+                next_frame = (next_frame + FRAME).max(now);
+                continue;
+            }
+            messages.recv_timeout(next_frame - now)
+        } else {
+            next_frame = Instant::now();
+            messages.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        };
+        match message {
+            Ok(message) => adapter.handle(&message),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
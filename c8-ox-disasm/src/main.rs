@@ -0,0 +1,198 @@
+use c8_ox_core::{decode_at, ControlFlow, Coverage, Instruction, Rom, SymbolTable, Syntax};
+use std::{collections::BTreeSet, env, fs, process};
+
+const USAGE: &str = "Usage: c8-ox-disasm [--octo] [--load-address <hex>] [--coverage <file>] \
+                     [--symbols <file>] [--cfg dot|json] <file-path>";
+
+// Formats of the control flow graph printed instead of the listing
+enum Graph {
+    Dot,
+    Json,
+}
+
+// Name of the label at a jump target without a symbol
+fn label_name(addr: u16) -> String {
+    format!("L{:03X}", addr)
+}
+
+// One line of the listing, either an instruction or a byte. Bytes are data according to the coverage
+// file, or left over at the end.
+enum Line {
+    Instruction(Instruction),
+    Byte(u8),
+}
+
+// Decodes the program linearly from its start. Data between the code is decoded as well, unless
+// the coverage of a run, which emulated the program, tells it apart.
+fn decode_all(data: &[u8], load_address: usize, coverage: &Coverage) -> Vec<(usize, Line)> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let addr = load_address + offset;
+        let instruction = decode_at(data, offset).filter(|instruction| {
+            (addr..addr + instruction.size()).all(|addr| !coverage.at(addr).is_data())
+        });
+        match instruction {
+            Some(instruction) => {
+                lines.push((addr, Line::Instruction(instruction)));
+                offset += instruction.size();
+            }
+            None => {
+                lines.push((addr, Line::Byte(data[offset])));
+                offset += 1;
+            }
+        }
+    }
+    lines
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut syntax = Syntax::Mnemonic;
+    let mut load_address = 0x200;
+    let mut coverage = Coverage::new();
+    let mut symbols = None;
+    let mut graph = None;
+    let mut path = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--octo" => syntax = Syntax::Octo,
+            "--load-address" => {
+                let addr = iter.next().map(|addr| addr.trim_start_matches("0x"));
+                match addr.and_then(|addr| usize::from_str_radix(addr, 16).ok()) {
+                    Some(addr) => load_address = addr,
+                    None => {
+                        eprintln!("--load-address expects a hexadecimal address");
+                        process::exit(1);
+                    }
+                }
+            }
+            "--coverage" => {
+                let file = iter.next().unwrap_or_else(|| {
+                    eprintln!("--coverage expects a file path");
+                    process::exit(1);
+                });
+                let text = fs::read_to_string(file).unwrap_or_else(|error| {
+                    eprintln!("Unable to open {}: {}", file, error);
+                    process::exit(1);
+                });
+                coverage = Coverage::parse(&text).unwrap_or_else(|error| {
+                    eprintln!("Unable to load {}: {}", file, error);
+                    process::exit(1);
+                });
+            }
+            "--symbols" => {
+                let file = iter.next().unwrap_or_else(|| {
+                    eprintln!("--symbols expects a file path");
+                    process::exit(1);
+                });
+                let text = fs::read_to_string(file).unwrap_or_else(|error| {
+                    eprintln!("Unable to open {}: {}", file, error);
+                    process::exit(1);
+                });
+                symbols = Some(SymbolTable::parse(&text).unwrap_or_else(|error| {
+                    eprintln!("Unable to load {}: {}", file, error);
+                    process::exit(1);
+                }));
+            }
+            "--cfg" => {
+                graph = match iter.next().map(String::as_str) {
+                    Some("dot") => Some(Graph::Dot),
+                    Some("json") => Some(Graph::Json),
+                    _ => {
+                        eprintln!("--cfg expects dot or json");
+                        process::exit(1);
+                    }
+                }
+            }
+            _ if arg.starts_with("--") => {
+                eprintln!("Unknown option {}\n{}", arg, USAGE);
+                process::exit(1);
+            }
+            _ => path = Some(arg.clone()),
+        }
+    }
+    let path = path.unwrap_or_else(|| {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    });
+
+    let rom = match Rom::read(&path) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("Unable to open {}: {}", path, error);
+            process::exit(1);
+        }
+    };
+
+    if let Some(graph) = graph {
+        let flow = ControlFlow::analyze(rom.data(), load_address);
+        match graph {
+            Graph::Dot => print!("{}", flow.to_dot(&path)),
+            Graph::Json => print!("{}", flow.to_json()),
+        }
+        return;
+    }
+
+    let lines = decode_all(rom.data(), load_address, &coverage);
+
+    // Without a symbol file, called subroutines are named like sub_2A4
+    let symbols = symbols.unwrap_or_else(|| SymbolTable::generate(rom.data(), load_address));
+
+    // Symbols and jump and call targets get labels, as long as a line starts there
+    let starts: BTreeSet<usize> = lines.iter().map(|(addr, _)| *addr).collect();
+    let targets: BTreeSet<u16> = lines
+        .iter()
+        .filter_map(|(_, line)| match line {
+            Line::Instruction(instruction) => instruction.target(),
+            Line::Byte(_) => None,
+        })
+        .collect();
+    let label = |addr: u16| {
+        if !starts.contains(&(addr as usize)) {
+            return None;
+        }
+        match symbols.label_at(addr as usize) {
+            Some(name) => Some(name.to_string()),
+            None if targets.contains(&addr) => Some(label_name(addr)),
+            None => None,
+        }
+    };
+
+    let comment = match syntax {
+        Syntax::Mnemonic => ';',
+        Syntax::Octo => '#',
+    };
+    println!("{} {}", comment, path);
+    println!("{} SHA-1 {}, {} byte", comment, rom.sha1_hex(), rom.size());
+
+    for (addr, line) in lines.iter() {
+        if let Some(name) = label(*addr as u16) {
+            match syntax {
+                Syntax::Mnemonic => println!("{}:", name),
+                Syntax::Octo => println!(": {}", name),
+            }
+        }
+
+        let (bytes, text) = match line {
+            Line::Instruction(instruction) => {
+                let bytes: Vec<String> = instruction
+                    .encode()
+                    .iter()
+                    .map(|opcode| format!("{:04X}", opcode))
+                    .collect();
+                (bytes.join(" "), instruction.format(syntax, &label))
+            }
+            Line::Byte(byte) => {
+                let text = match syntax {
+                    Syntax::Mnemonic => format!("DB #{:02X}", byte),
+                    Syntax::Octo => format!("0x{:02X}", byte),
+                };
+                (format!("{:02X}", byte), text)
+            }
+        };
+        println!("    {:<28}{} {:04X}: {}", text, comment, addr, bytes);
+    }
+}
@@ -0,0 +1,392 @@
+use crate::debug::DebugFrontend;
+use crate::options::parse_hex;
+use c8_ox_core::{
+    decode_at, Access, Condition, Debugger, ExecutionError, SChip8, Stop, SymbolTable, Syntax,
+    Watchpoint,
+};
+use std::io::{self, BufRead, Write};
+use std::{sync::mpsc, thread};
+
+const HELP: &str = "\
+Numbers and addresses are hexadecimal, an empty line repeats the last command.
+Addresses can also be given as labels, like b main-loop.
+  c, continue          run until the next breakpoint
+  s, step              execute one instruction
+  n, next              step, running a called subroutine up to its return
+  o, out               run until the current subroutine returns
+  u, until <addr>      run until the program counter reaches the address
+  p, pause             stop the running program
+  b, break [<addr> [if <condition>]]
+                       set a breakpoint, or list them without an address. The condition
+                       compares registers, bytes like [2F0] and numbers with == != < <= > >=,
+                       joined by && and ||, e.g. b 2A4 if V3 == 10 && DT == 0
+  d, delete <addr>     remove a breakpoint
+  watch [<addr> [len]] stop after the memory was written, or list the watchpoints
+  rwatch <addr> [len]  stop after the memory was read
+  awatch <addr> [len]  stop after the memory was read or written
+  unwatch <addr>       remove the watchpoints at the address
+  r, reg               show the registers
+  set <reg> <value>    change V0-VF, I, PC, DT or ST
+  stack                show the return addresses
+  m, mem <addr> [len]  show memory
+  w, write <addr> <byte>...  change memory
+  l, dis [addr] [count]      disassemble, around the program counter by default
+  disp                 show the display as text
+  h, help              show this help
+  q, quit              quit the emulator";
+
+// Bytes shown by mem without a length
+const MEM_LEN: usize = 0x40;
+// Instructions shown by dis without a count
+const DIS_COUNT: usize = 8;
+
+// Interactive debugger on the terminal, enabled with --debug.
+// Commands are read on a separate thread, so the window keeps updating while the program is paused.
+pub struct DebugConsole {
+    debugger: Debugger,
+    symbols: SymbolTable, // Labels shown for addresses and accepted instead of them
+    commands: mpsc::Receiver<String>,
+    last_command: String, // Repeated by an empty line
+}
+
+impl DebugConsole {
+    pub fn new(schip8: &SChip8, symbols: SymbolTable) -> DebugConsole {
+        let (sender, commands) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        println!("----- SCHIP8 Oxidized Interactive Debugger -----");
+        println!("Type h for the available commands");
+        let console = DebugConsole {
+            debugger: Debugger::new(),
+            symbols,
+            commands,
+            last_command: String::new(),
+        };
+        console.show_location(schip8);
+        console
+    }
+
+    fn execute(&mut self, line: &str, schip8: &mut SChip8) -> Result<bool, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+        match command {
+            "" => {}
+            "c" | "continue" => self.debugger.resume(),
+            "s" | "step" => self.debugger.step(),
+            "n" | "next" => self.debugger.step_over(schip8),
+            "o" | "out" => {
+                if !self.debugger.step_out(schip8) {
+                    return Err("Not inside a subroutine".to_string());
+                }
+            }
+            "u" | "until" => {
+                let addr = self.address(schip8, args.first())?;
+                self.debugger.run_to(addr);
+            }
+            "p" | "pause" => {
+                self.debugger.pause();
+                self.show_location(schip8);
+            }
+            "b" | "break" if args.is_empty() => {
+                for (addr, condition) in self.debugger.breakpoints() {
+                    match condition {
+                        Some(condition) => {
+                            println!("Breakpoint at {} if {}", self.name(addr), condition)
+                        }
+                        None => println!("Breakpoint at {}", self.name(addr)),
+                    }
+                }
+            }
+            "b" | "break" => {
+                let addr = self.address(schip8, args.first())?;
+                let condition = match args.get(1) {
+                    Some(&"if") => {
+                        let text = args[2..].join(" ");
+                        let condition = Condition::parse(&text)
+                            .ok_or_else(|| format!("Invalid condition {}", text))?;
+                        Some(condition)
+                    }
+                    Some(_) => return Err("Expected if and a condition".to_string()),
+                    None => None,
+                };
+                if !self.debugger.add_breakpoint(addr, condition) {
+                    println!("Replaced the breakpoint at {}", self.name(addr));
+                }
+            }
+            "d" | "delete" => {
+                let addr = self.address(schip8, args.first())?;
+                if !self.debugger.remove_breakpoint(addr) {
+                    return Err(format!("There is no breakpoint at {}", self.name(addr)));
+                }
+            }
+            "watch" if args.is_empty() => {
+                for watchpoint in self.debugger.watchpoints() {
+                    let kind = match (watchpoint.read, watchpoint.write) {
+                        (true, true) => "Access",
+                        (true, false) => "Read",
+                        _ => "Write",
+                    };
+                    println!(
+                        "{} watchpoint at {}, {:X} byte",
+                        kind,
+                        self.name(watchpoint.addr),
+                        watchpoint.len
+                    );
+                }
+            }
+            "watch" | "rwatch" | "awatch" => {
+                let addr = self.address(schip8, args.first())?;
+                let len = args.get(1).map_or(Ok(1), |len| parse_hex(len))?;
+                if len == 0 {
+                    return Err("A watchpoint needs at least one byte".to_string());
+                }
+                self.debugger.add_watchpoint(Watchpoint {
+                    addr,
+                    len,
+                    read: command != "watch",
+                    write: command != "rwatch",
+                });
+            }
+            "unwatch" => {
+                let addr = self.address(schip8, args.first())?;
+                if !self.debugger.remove_watchpoint(addr) {
+                    return Err(format!("There is no watchpoint at {}", self.name(addr)));
+                }
+            }
+            "r" | "reg" => show_registers(schip8),
+            "set" => {
+                let (register, value) = match args[..] {
+                    [register, value] => (register, parse_hex(value)?),
+                    _ => return Err("set expects a register and a value".to_string()),
+                };
+                set_register(schip8, register, value)?;
+            }
+            "stack" => {
+                for (depth, addr) in schip8.stack().iter().enumerate().rev() {
+                    println!("{:02X}: {}", depth, self.name(*addr));
+                }
+            }
+            "m" | "mem" => {
+                let addr = self.address(schip8, args.first())?;
+                let len = args.get(1).map_or(Ok(MEM_LEN), |len| parse_hex(len))?;
+                let end = addr.saturating_add(len).min(schip8.memory().len());
+                for (row, bytes) in schip8.memory()[addr..end].chunks(16).enumerate() {
+                    let bytes: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+                    println!("{:04X}: {}", addr + row * 16, bytes.join(" "));
+                }
+            }
+            "w" | "write" => {
+                let addr = self.address(schip8, args.first())?;
+                let bytes = args[1..]
+                    .iter()
+                    .map(|byte| match parse_hex(byte)? {
+                        byte if byte <= 0xFF => Ok(byte as u8),
+                        _ => Err(format!("{} is not a byte", byte)),
+                    })
+                    .collect::<Result<Vec<u8>, String>>()?;
+                if addr + bytes.len() > schip8.memory().len() {
+                    return Err("The bytes do not fit into the memory".to_string());
+                }
+                schip8.memory_mut()[addr..addr + bytes.len()].copy_from_slice(&bytes);
+            }
+            "l" | "dis" => {
+                let start = match args.first() {
+                    Some(_) => self.address(schip8, args.first())?,
+                    None => schip8.pc().saturating_sub(DIS_COUNT / 2 * 2),
+                };
+                let count = args
+                    .get(1)
+                    .map_or(Ok(DIS_COUNT), |count| parse_hex(count))?;
+                self.disassemble(schip8, start, count);
+            }
+            "disp" => {
+                let display = schip8.display();
+                for row in display.pixels().chunks(display.width()) {
+                    let row: String = row.iter().map(|pixel| pixel.to_string()).collect();
+                    println!("{}", row);
+                }
+            }
+            "h" | "help" => println!("{}", HELP),
+            "q" | "quit" => return Ok(false),
+            _ => return Err(format!("Unknown command {}, type h for help", command)),
+        }
+        Ok(true)
+    }
+
+    // Registers and the instructions around the program counter, followed by the prompt
+    fn show_location(&self, schip8: &SChip8) {
+        show_registers(schip8);
+        let start = schip8.pc().saturating_sub(4);
+        self.disassemble(schip8, start, 5);
+        self.prompt(schip8);
+    }
+
+    // The prompt names the location of the program counter, like main-loop+4>
+    fn prompt(&self, schip8: &SChip8) {
+        print!(
+            "{}> ",
+            self.symbols.describe(schip8.pc()).unwrap_or_default()
+        );
+        let _ = io::stdout().flush();
+    }
+
+    // The address followed by its label, like 02A4 (main-loop)
+    fn name(&self, addr: usize) -> String {
+        match self.symbols.label_at(addr) {
+            Some(label) => format!("{:04X} ({})", addr, label),
+            None => format!("{:04X}", addr),
+        }
+    }
+
+    // An address inside the memory of the machine, given as a label or in hexadecimal
+    fn address(&self, schip8: &SChip8, arg: Option<&&str>) -> Result<usize, String> {
+        let arg = arg.ok_or("Expected an address")?;
+        let addr = match self.symbols.addr_of(arg) {
+            Some(addr) => addr,
+            None => {
+                parse_hex(arg).map_err(|_| format!("{} is neither a label nor an address", arg))?
+            }
+        };
+        if addr >= schip8.memory().len() {
+            return Err(format!("{:X} is outside the memory", addr));
+        }
+        Ok(addr)
+    }
+
+    // Lists count instructions, marking the program counter with > and breakpoints with *
+    fn disassemble(&self, schip8: &SChip8, start: usize, count: usize) {
+        let memory = schip8.memory();
+        let label = |addr: u16| self.symbols.label_at(addr as usize).map(str::to_string);
+        let mut addr = start;
+        for _ in 0..count {
+            let instruction = match decode_at(memory, addr) {
+                Some(instruction) => instruction,
+                None => break,
+            };
+            if let Some(label) = self.symbols.label_at(addr) {
+                println!("{}:", label);
+            }
+            let current = if addr == schip8.pc() { '>' } else { ' ' };
+            let breakpoint = if self.debugger.has_breakpoint(addr) {
+                '*'
+            } else {
+                ' '
+            };
+            let bytes: String = memory[addr..addr + instruction.size()]
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect();
+            println!(
+                "{}{} {:04X}: {:<8} {}",
+                current,
+                breakpoint,
+                addr,
+                bytes,
+                instruction.format(Syntax::Mnemonic, &label)
+            );
+            addr += instruction.size();
+        }
+    }
+}
+
+impl DebugFrontend for DebugConsole {
+    fn debugger(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    fn stopped(&mut self, schip8: &SChip8) {
+        match self.debugger.stop() {
+            Stop::Breakpoint => println!("Breakpoint at {}", self.name(schip8.pc())),
+            Stop::Watchpoint { addr, access, pc } => {
+                let accessed = match access {
+                    Access::Sprite => "drawn",
+                    Access::Read => "read",
+                    Access::Write => "written",
+                };
+                println!(
+                    "{} was {} by the instruction at {}",
+                    self.name(addr),
+                    accessed,
+                    self.name(pc)
+                );
+            }
+            Stop::Pause | Stop::Step => {}
+        }
+        self.show_location(schip8);
+    }
+
+    fn crashed(&mut self, error: &ExecutionError, schip8: &SChip8) {
+        println!("The ROM crashed: {}", error);
+        self.debugger.pause();
+        self.show_location(schip8);
+    }
+
+    // After a command resuming the program the others wait for the next call, so it runs in between
+    fn update(&mut self, schip8: &mut SChip8) -> bool {
+        while let Ok(line) = self.commands.try_recv() {
+            if !line.trim().is_empty() {
+                self.last_command = line.trim().to_string();
+            }
+            let command = self.last_command.clone();
+            match self.execute(&command, schip8) {
+                Ok(true) => {}
+                Ok(false) => return false,
+                Err(message) => println!("{}", message),
+            }
+            if !self.debugger.paused() {
+                break;
+            }
+            self.prompt(schip8);
+        }
+        true
+    }
+}
+
+fn show_registers(schip8: &SChip8) {
+    println!(
+        "PC: {:04X}  I: {:04X}  SP: {:X}  DT: {:02X}  ST: {:02X}",
+        schip8.pc(),
+        schip8.i(),
+        schip8.sp(),
+        schip8.dt(),
+        schip8.st()
+    );
+    let v: Vec<String> = (0..16)
+        .map(|x| format!("V{:X}: {:02X}", x, schip8.v(x)))
+        .collect();
+    println!("{}", v[..8].join("  "));
+    println!("{}", v[8..].join("  "));
+}
+
+fn set_register(schip8: &mut SChip8, register: &str, value: usize) -> Result<(), String> {
+    let register = register.to_uppercase();
+    let limit = match register.as_str() {
+        "I" => 0xFFFF,
+        "PC" => schip8.memory().len() - 2,
+        _ => 0xFF,
+    };
+    if value > limit {
+        return Err(format!("{:X} is out of range for {}", value, register));
+    }
+    match register.as_str() {
+        "I" => schip8.set_i(value as u16),
+        "PC" => schip8.set_pc(value),
+        "DT" => schip8.set_dt(value as u8),
+        "ST" => schip8.set_st(value as u8),
+        _ => match register
+            .strip_prefix('V')
+            .map(|x| usize::from_str_radix(x, 16))
+        {
+            Some(Ok(x)) if x < 16 && register.len() == 2 => schip8.set_v(x, value as u8),
+            _ => return Err(format!("Unknown register {}", register)),
+        },
+    }
+    Ok(())
+}
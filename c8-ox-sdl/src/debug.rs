@@ -0,0 +1,21 @@
+use c8_ox_core::{Debugger, ExecutionError, SChip8};
+
+// A way to control the debugger from outside, like the terminal console or a GDB client.
+// The main loop runs frames with SChip8::run_frame_with(key, frontend.debugger()) while it is not
+// paused and reports back what happened.
+pub trait DebugFrontend {
+    // Hooks for SChip8::run_frame_with
+    fn debugger(&mut self) -> &mut Debugger;
+
+    // Handles the requests received since the last call, returns false once the user quits
+    fn update(&mut self, schip8: &mut SChip8) -> bool;
+
+    // The debugger stopped the program during the last frame
+    fn stopped(&mut self, schip8: &SChip8);
+
+    // Pauses at a faulting instruction instead of quitting, the machine is left as it was before it
+    fn crashed(&mut self, error: &ExecutionError, schip8: &SChip8);
+
+    // The program exited, the emulator quits afterwards
+    fn exited(&mut self) {}
+}
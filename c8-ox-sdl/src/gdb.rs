@@ -0,0 +1,636 @@
+use crate::debug::DebugFrontend;
+use c8_ox_core::{Access, Debugger, ExecutionError, Fault, SChip8, Stop, Watchpoint};
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+// Registers in the order of the g packet, with their size in bytes.
+// Values are sent big endian, the byte order of the CHIP-8.
+const REGISTERS: [(&str, usize); 21] = [
+    ("v0", 1),
+    ("v1", 1),
+    ("v2", 1),
+    ("v3", 1),
+    ("v4", 1),
+    ("v5", 1),
+    ("v6", 1),
+    ("v7", 1),
+    ("v8", 1),
+    ("v9", 1),
+    ("va", 1),
+    ("vb", 1),
+    ("vc", 1),
+    ("vd", 1),
+    ("ve", 1),
+    ("vf", 1),
+    ("i", 2),
+    ("pc", 2),
+    ("sp", 1),
+    ("dt", 1),
+    ("st", 1),
+];
+
+// Signals reported in stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+// Stub for the GDB remote serial protocol, enabled with --gdb <port>.
+// Waits for a client like gdb or lldb to connect, then serves it from the main loop without blocking.
+// The connection is a TcpStream, or a byte buffer in the tests.
+pub struct GdbStub<S = TcpStream> {
+    debugger: Debugger,
+    stream: Option<S>,  // None once the client detached
+    input: Vec<u8>,     // Received bytes not handled yet
+    ack: bool,          // Whether packets are acknowledged, until QStartNoAckMode
+    stop_reply: String, // Answer to ?, the reason of the last stop
+}
+
+impl GdbStub {
+    // Blocks until a client connected
+    pub fn listen(port: u16) -> Result<GdbStub, io::Error> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        println!("Waiting for a GDB client on port {}", port);
+        let (stream, addr) = listener.accept()?;
+        println!("GDB client connected from {}", addr);
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(GdbStub::new(stream))
+    }
+}
+
+impl<S: Write> GdbStub<S> {
+    fn new(stream: S) -> GdbStub<S> {
+        GdbStub {
+            debugger: Debugger::new(),
+            stream: Some(stream),
+            input: Vec::new(),
+            ack: true,
+            stop_reply: format!("S{:02x}", SIGTRAP),
+        }
+    }
+
+    fn send(&mut self, data: &str) {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        self.write(format!("${}#{:02x}", data, checksum).as_bytes());
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        if let Some(stream) = &mut self.stream {
+            // The socket is non-blocking, so a full send buffer has to be waited for
+            let mut bytes = bytes;
+            while !bytes.is_empty() {
+                match stream.write(bytes) {
+                    Ok(written) => bytes = &bytes[written..],
+                    Err(error) if error.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(_) => {
+                        self.disconnect();
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    // Lets the program run on without a debugger
+    fn disconnect(&mut self) {
+        println!("GDB client disconnected");
+        self.stream = None;
+        self.debugger = Debugger::new();
+        self.debugger.resume();
+    }
+
+    fn stop_with(&mut self, reply: String) {
+        self.stop_reply = reply.clone();
+        self.send(&reply);
+    }
+
+    // Takes the next complete packet from the input, answering acknowledgements and interrupts
+    fn next_packet(&mut self) -> Option<String> {
+        loop {
+            match *self.input.first()? {
+                b'$' => {
+                    let end = self.input.iter().position(|&byte| byte == b'#')?;
+                    if self.input.len() < end + 3 {
+                        return None;
+                    }
+                    let packet: Vec<u8> = self.input.drain(..end + 3).collect();
+                    let data = &packet[1..end];
+                    let checksum = std::str::from_utf8(&packet[end + 1..])
+                        .ok()
+                        .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+                    let valid =
+                        checksum == Some(data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)));
+                    if self.ack {
+                        self.write(if valid { b"+" } else { b"-" });
+                    }
+                    if valid {
+                        return Some(String::from_utf8_lossy(data).into_owned());
+                    }
+                }
+                // Ctrl-C in the client
+                0x03 => {
+                    self.input.remove(0);
+                    if !self.debugger.paused() {
+                        self.debugger.pause();
+                        self.stop_with(format!("S{:02x}", SIGINT));
+                    }
+                }
+                // Acknowledgements, packets are not sent again
+                _ => {
+                    self.input.remove(0);
+                }
+            }
+        }
+    }
+
+    // Answers a packet, returns false if the client killed the program
+    fn handle(&mut self, packet: &str, schip8: &mut SChip8) -> bool {
+        let command = packet.get(..1).unwrap_or("");
+        let args = packet.get(1..).unwrap_or("");
+        let reply = match command {
+            "?" => self.stop_reply.clone(),
+            "g" => REGISTERS
+                .iter()
+                .enumerate()
+                .map(|(n, &(_, size))| to_hex(register(schip8, n), size))
+                .collect(),
+            "G" => {
+                let mut args = args;
+                for (n, &(_, size)) in REGISTERS.iter().enumerate() {
+                    match args.get(..size * 2).and_then(from_hex) {
+                        Some(value) if set_register(schip8, n, value) => {}
+                        _ => return self.error(),
+                    }
+                    args = &args[size * 2..];
+                }
+                "OK".to_string()
+            }
+            "p" => match from_hex(args) {
+                Some(n) if n < REGISTERS.len() => to_hex(register(schip8, n), REGISTERS[n].1),
+                _ => "E01".to_string(),
+            },
+            "P" => match args.split_once('=') {
+                Some((n, value)) => match (from_hex(n), from_hex(value)) {
+                    (Some(n), Some(value)) if n < REGISTERS.len() => {
+                        if set_register(schip8, n, value) {
+                            "OK".to_string()
+                        } else {
+                            "E01".to_string()
+                        }
+                    }
+                    _ => "E01".to_string(),
+                },
+                None => "E01".to_string(),
+            },
+            "m" => match memory_range(schip8, args) {
+                Some((addr, len)) => schip8.memory()[addr..addr + len]
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect(),
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let data = args.split_once(':').and_then(|(range, data)| {
+                    let (addr, len) = memory_range(schip8, range)?;
+                    let bytes = (0..len)
+                        .map(|i| data.get(i * 2..i * 2 + 2).and_then(from_hex))
+                        .collect::<Option<Vec<usize>>>()?;
+                    Some((addr, bytes))
+                });
+                match data {
+                    Some((addr, bytes)) => {
+                        for (offset, byte) in bytes.into_iter().enumerate() {
+                            schip8.memory_mut()[addr + offset] = byte as u8;
+                        }
+                        "OK".to_string()
+                    }
+                    None => "E01".to_string(),
+                }
+            }
+            "c" | "s" => {
+                if !args.is_empty() {
+                    match from_hex(args) {
+                        Some(addr) if valid_pc(schip8, addr) => schip8.set_pc(addr),
+                        _ => return self.error(),
+                    }
+                }
+                self.resume(command == "s");
+                return true;
+            }
+            "Z" | "z" => self.breakpoint(command == "Z", args, schip8.memory().len()),
+            "k" => return false,
+            "D" => {
+                self.send("OK");
+                self.disconnect();
+                return true;
+            }
+            // Threads are ignored, there is only one
+            "v" if packet.starts_with("vCont;") => {
+                self.resume(packet.starts_with("vCont;s"));
+                return true;
+            }
+            "H" | "T" => "OK".to_string(),
+            _ => self.query(packet),
+        };
+        self.send(&reply);
+        true
+    }
+
+    // Answers the longer packets starting with q, Q or v
+    fn query(&mut self, packet: &str) -> String {
+        match packet {
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            "QStartNoAckMode" => {
+                self.ack = false;
+                "OK".to_string()
+            }
+            "vCont?" => "vCont;c;s".to_string(),
+            _ if packet.starts_with("qSupported") => {
+                "PacketSize=1000;QStartNoAckMode+;qXfer:features:read+".to_string()
+            }
+            _ if packet.starts_with("qXfer:features:read:target.xml:") => {
+                let range = &packet["qXfer:features:read:target.xml:".len()..];
+                let xml = target_xml();
+                match range
+                    .split_once(',')
+                    .map(|(o, l)| (from_hex(o), from_hex(l)))
+                {
+                    Some((Some(offset), Some(len))) if offset <= xml.len() => {
+                        let end = (offset + len).min(xml.len());
+                        let prefix = if end == xml.len() { 'l' } else { 'm' };
+                        format!("{}{}", prefix, &xml[offset..end])
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            // Not supported
+            _ => String::new(),
+        }
+    }
+
+    // Z0 and Z1 set breakpoints, Z2 to Z4 set write, read and access watchpoints
+    fn breakpoint(&mut self, insert: bool, args: &str, memory_size: usize) -> String {
+        let mut args = args.split(',');
+        let (kind, addr, len) = match (args.next(), args.next(), args.next()) {
+            (Some(kind), Some(addr), Some(len)) => (kind, from_hex(addr), from_hex(len)),
+            _ => return "E01".to_string(),
+        };
+        let (addr, len) = match (addr, len) {
+            (Some(addr), Some(len)) if addr < memory_size && len <= memory_size - addr => {
+                (addr, len)
+            }
+            _ => return "E01".to_string(),
+        };
+        match (kind, insert) {
+            ("0" | "1", true) => {
+                self.debugger.add_breakpoint(addr, None);
+            }
+            ("0" | "1", false) => {
+                self.debugger.remove_breakpoint(addr);
+            }
+            ("2" | "3" | "4", true) => self.debugger.add_watchpoint(Watchpoint {
+                addr,
+                len: len.max(1),
+                read: kind != "2",
+                write: kind != "3",
+            }),
+            ("2" | "3" | "4", false) => {
+                self.debugger.remove_watchpoint(addr);
+            }
+            _ => return String::new(),
+        }
+        "OK".to_string()
+    }
+
+    fn resume(&mut self, step: bool) {
+        if step {
+            self.debugger.step();
+        } else {
+            self.debugger.resume();
+        }
+    }
+
+    fn error(&mut self) -> bool {
+        self.send("E01");
+        true
+    }
+}
+
+impl<S: Read + Write> DebugFrontend for GdbStub<S> {
+    fn debugger(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    fn update(&mut self, schip8: &mut SChip8) -> bool {
+        let mut buffer = [0; 4096];
+        while let Some(stream) = &mut self.stream {
+            match stream.read(&mut buffer) {
+                Ok(0) => self.disconnect(),
+                Ok(len) => self.input.extend_from_slice(&buffer[..len]),
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(_) => self.disconnect(),
+            }
+        }
+
+        // Like on the console, packets after one resuming the program wait until it stopped again
+        while self.debugger.paused() || self.input.first() == Some(&0x03) {
+            let packet = match self.next_packet() {
+                Some(packet) => packet,
+                None => break,
+            };
+            if !self.handle(&packet, schip8) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn stopped(&mut self, _schip8: &SChip8) {
+        let reply = match self.debugger.stop() {
+            Stop::Watchpoint { addr, access, .. } => {
+                let watchpoint = self
+                    .debugger
+                    .watchpoints()
+                    .iter()
+                    .find(|w| w.contains(addr));
+                let kind = match watchpoint {
+                    Some(w) if w.read && w.write => "awatch",
+                    _ if access == Access::Write => "watch",
+                    _ => "rwatch",
+                };
+                format!("T{:02x}{}:{:x};", SIGTRAP, kind, addr)
+            }
+            Stop::Pause => format!("S{:02x}", SIGINT),
+            Stop::Step | Stop::Breakpoint => format!("S{:02x}", SIGTRAP),
+        };
+        self.stop_with(reply);
+    }
+
+    fn crashed(&mut self, error: &ExecutionError, _schip8: &SChip8) {
+        println!("The ROM crashed: {}", error);
+        self.debugger.pause();
+        let signal = match error.fault {
+            Fault::UnknownInstruction | Fault::MachineCodeCall => SIGILL,
+            _ => SIGSEGV,
+        };
+        self.stop_with(format!("S{:02x}", signal));
+    }
+
+    fn exited(&mut self) {
+        self.send("W00");
+    }
+}
+
+fn register(schip8: &SChip8, n: usize) -> usize {
+    match n {
+        0..=15 => schip8.v(n) as usize,
+        16 => schip8.i() as usize,
+        17 => schip8.pc(),
+        18 => schip8.sp(),
+        19 => schip8.dt() as usize,
+        _ => schip8.st() as usize,
+    }
+}
+
+// Values are cut to the size of the register, returns false for a program counter outside the memory
+fn set_register(schip8: &mut SChip8, n: usize, value: usize) -> bool {
+    match n {
+        0..=15 => schip8.set_v(n, value as u8),
+        16 => schip8.set_i(value as u16),
+        17 if valid_pc(schip8, value) => schip8.set_pc(value),
+        17 => return false,
+        19 => schip8.set_dt(value as u8),
+        20 => schip8.set_st(value as u8),
+        // The stack pointer cannot be changed
+        _ => {}
+    }
+    true
+}
+
+// Whether a whole instruction fits into the memory at the address
+fn valid_pc(schip8: &SChip8, addr: usize) -> bool {
+    addr.checked_add(2)
+        .is_some_and(|end| end <= schip8.memory().len())
+}
+
+// Parses addr,len of m and M packets, cut to the memory
+fn memory_range(schip8: &SChip8, range: &str) -> Option<(usize, usize)> {
+    let (addr, len) = range.split_once(',')?;
+    let (addr, len) = (from_hex(addr)?, from_hex(len)?);
+    let size = schip8.memory().len();
+    if addr >= size {
+        return None;
+    }
+    Some((addr, len.min(size - addr)))
+}
+
+fn from_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+fn to_hex(value: usize, size: usize) -> String {
+    format!("{:01$x}", value, size * 2)
+}
+
+// Target description, telling the client the names and sizes of the registers
+fn target_xml() -> String {
+    let registers: String = REGISTERS
+        .iter()
+        .map(|(name, size)| {
+            format!(
+                "<reg name=\"{}\" bitsize=\"{}\" type=\"int\"/>",
+                name,
+                size * 8
+            )
+        })
+        .collect();
+    format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\"><feature name=\"org.chip8-oxidized.cpu\">{}</feature></target>",
+        registers
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use c8_ox_core::{Platform, Rom, SeededRandom};
+
+    fn stub() -> (GdbStub<Vec<u8>>, SChip8) {
+        let rom = Rom::new(vec![0x60, 0x2A, 0xA3, 0x00, 0x12, 0x02]).unwrap();
+        let schip8 = SChip8::new(&rom, &Platform::default(), Box::new(SeededRandom::new(1)));
+        (GdbStub::new(Vec::new()), schip8.unwrap())
+    }
+
+    fn packet(data: &str) -> String {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        format!("${}#{:02x}", data, checksum)
+    }
+
+    // Takes everything the stub sent so far
+    fn output(stub: &mut GdbStub<Vec<u8>>) -> String {
+        String::from_utf8(stub.stream.as_mut().unwrap().split_off(0)).unwrap()
+    }
+
+    // Sends a packet and returns the acknowledgement and reply
+    fn request(stub: &mut GdbStub<Vec<u8>>, schip8: &mut SChip8, data: &str) -> String {
+        stub.input.extend_from_slice(packet(data).as_bytes());
+        let received = stub.next_packet().unwrap();
+        assert_eq!(received, data);
+        assert!(stub.handle(&received, schip8));
+        output(stub)
+    }
+
+    #[test]
+    fn frames_packets_and_checks_checksums() {
+        let (mut stub, _) = stub();
+
+        // Split across reads, with an acknowledgement in front
+        stub.input.extend_from_slice(b"+$qC#");
+        assert_eq!(stub.next_packet(), None);
+        stub.input.extend_from_slice(b"b");
+        assert_eq!(stub.next_packet(), None);
+        stub.input.extend_from_slice(b"4");
+        assert_eq!(stub.next_packet(), Some("qC".to_string()));
+        assert_eq!(output(&mut stub), "+");
+
+        // Rejected, then the packet after it is taken
+        stub.input.extend_from_slice(b"$qC#00");
+        stub.input.extend_from_slice(packet("g").as_bytes());
+        assert_eq!(stub.next_packet(), Some("g".to_string()));
+        assert_eq!(output(&mut stub), "-+");
+        assert!(stub.input.is_empty());
+    }
+
+    #[test]
+    fn stops_acknowledging_in_no_ack_mode() {
+        let (mut stub, mut schip8) = stub();
+        assert_eq!(
+            request(&mut stub, &mut schip8, "QStartNoAckMode"),
+            format!("+{}", packet("OK"))
+        );
+        assert_eq!(request(&mut stub, &mut schip8, "qC"), packet("QC1"));
+        stub.input.extend_from_slice(b"$qC#00");
+        assert_eq!(stub.next_packet(), None);
+        assert_eq!(output(&mut stub), "");
+    }
+
+    #[test]
+    fn reads_and_writes_registers() {
+        let (mut stub, mut schip8) = stub();
+        schip8.set_v(0, 0x2A);
+        schip8.set_v(15, 0x01);
+        schip8.set_i(0x300);
+        schip8.set_dt(0x10);
+        let registers = format!(
+            "2a{}01{}{}{}{}{}",
+            "00".repeat(14),
+            "0300",
+            "0200",
+            "00",
+            "10",
+            "00"
+        );
+        assert_eq!(
+            request(&mut stub, &mut schip8, "g"),
+            format!("+{}", packet(&registers))
+        );
+
+        assert_eq!(
+            request(&mut stub, &mut schip8, "P11=0204"),
+            format!("+{}", packet("OK"))
+        );
+        assert_eq!(schip8.pc(), 0x204);
+        assert_eq!(
+            request(&mut stub, &mut schip8, "p11"),
+            format!("+{}", packet("0204"))
+        );
+
+        // Program counters outside the memory
+        for data in [
+            "P11=ffffffffffffffff",
+            "P11=fff",
+            "cffffffffffffffff",
+            "s10000",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                request(&mut stub, &mut schip8, data),
+                format!("+{}", packet("E01"))
+            );
+        }
+        assert_eq!(schip8.pc(), 0x204);
+        assert!(stub.debugger.paused());
+    }
+
+    #[test]
+    fn reads_memory() {
+        let (mut stub, mut schip8) = stub();
+        assert_eq!(
+            request(&mut stub, &mut schip8, "m200,6"),
+            format!("+{}", packet("602aa3001202"))
+        );
+        // Cut to the end of the memory
+        assert_eq!(
+            request(&mut stub, &mut schip8, "mffe,8"),
+            format!("+{}", packet("0000"))
+        );
+        assert_eq!(
+            request(&mut stub, &mut schip8, "m1000,1"),
+            format!("+{}", packet("E01"))
+        );
+    }
+
+    #[test]
+    fn sets_breakpoints_and_watchpoints() {
+        let (mut stub, mut schip8) = stub();
+        let ok = format!("+{}", packet("OK"));
+        let error = format!("+{}", packet("E01"));
+        assert_eq!(request(&mut stub, &mut schip8, "Z0,204,2"), ok);
+        assert!(stub.debugger.has_breakpoint(0x204));
+        assert_eq!(request(&mut stub, &mut schip8, "z0,204,2"), ok);
+        assert!(!stub.debugger.has_breakpoint(0x204));
+
+        assert_eq!(request(&mut stub, &mut schip8, "Z2,300,4"), ok);
+        let watchpoint = Watchpoint {
+            addr: 0x300,
+            len: 4,
+            read: false,
+            write: true,
+        };
+        assert_eq!(stub.debugger.watchpoints(), [watchpoint]);
+
+        // Ranges outside the memory
+        for data in [
+            "Z2,ffffffffffffffff,10",
+            "Z3,ff0,ffffffffffffffff",
+            "Z4,1000,1",
+            "Z0,ffffffffffffffff,2",
+        ]
+        .iter()
+        {
+            assert_eq!(request(&mut stub, &mut schip8, data), error);
+        }
+        assert_eq!(stub.debugger.watchpoints(), [watchpoint]);
+    }
+
+    #[test]
+    fn interrupts_the_running_program() {
+        let (mut stub, mut schip8) = stub();
+        assert_eq!(request(&mut stub, &mut schip8, "c"), "+");
+        assert!(!stub.debugger.paused());
+        stub.input.push(0x03);
+        assert_eq!(stub.next_packet(), None);
+        assert!(stub.debugger.paused());
+        assert_eq!(output(&mut stub), packet("S02"));
+        assert_eq!(
+            request(&mut stub, &mut schip8, "?"),
+            format!("+{}", packet("S02"))
+        );
+    }
+}
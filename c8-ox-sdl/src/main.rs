@@ -1,29 +1,261 @@
-mod schip8;
+mod console;
+mod debug;
+mod gdb;
+mod options;
+mod storage;
 
-use schip8::SChip8;
-use sdl2::{audio, event, keyboard::Keycode, pixels};
-use std::{collections::HashMap, env, fs, io, time::Duration, time::SystemTime};
+use c8_ox_core::{
+    Audio, Coverage, ExecutionError, Movie, Profiler, Rewind, Rom, SChip8, SeededRandom,
+    SymbolTable, Tracer, MEMORY_MAP_WIDTH, PATTERN_BITS,
+};
+use console::DebugConsole;
+use debug::DebugFrontend;
+use gdb::GdbStub;
+use options::{Options, USAGE};
+use sdl2::{audio, event, keyboard::Keycode, keyboard::Mod, messagebox, pixels, video};
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::{collections::HashMap, env, time::Duration, time::SystemTime};
+use storage::{FileFlags, Slots, NUM_SLOTS};
 
-// Squarewave for audio output
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
+// 1-bit wave playing the audio pattern of the emulator for audio output
+struct PatternWave {
+    audio: Audio,     // Copy of the pattern and pitch, updated by the emulator loop
+    sample_rate: f32, //
+    position: f32,    // Position in the pattern in bits
+    volume: f32,      //
 }
 
-impl audio::AudioCallback for SquareWave {
+impl audio::AudioCallback for PatternWave {
     // Data channel
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate square wave
+        // The position carries over between buffers and pattern changes, so there is no click
+        let step = self.audio.rate() / self.sample_rate;
         for x in out.iter_mut() {
-            if self.phase <= 0.5 {
+            if self.audio.sample(self.position as usize) == 1 {
                 *x = self.volume;
             } else {
                 *x = -self.volume;
             };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+            self.position = (self.position + step) % PATTERN_BITS as f32;
+        }
+    }
+}
+
+// Passed to the core when no key was pressed during a frame
+const NO_KEY: usize = 0x10;
+
+// Entries of each ranking in the profile report
+const PROFILE_REPORT_LINES: usize = 20;
+
+// Pixels per byte in both directions on the memory map of the coverage
+const MEMORY_MAP_SCALE: usize = 4;
+
+// Holding this key steps backwards through the recorded frames
+const REWIND_KEY: Keycode = Keycode::Backspace;
+// How far back the rewind reaches, in 60 Hz frames
+const REWIND_FRAMES: usize = 30 * 60;
+
+// Quick-save slot keys, pressing one loads the slot and pressing it with shift saves to it
+const SLOT_KEYS: [Keycode; NUM_SLOTS] = [
+    Keycode::F1,
+    Keycode::F2,
+    Keycode::F3,
+    Keycode::F4,
+    Keycode::F5,
+    Keycode::F6,
+    Keycode::F7,
+    Keycode::F8,
+    Keycode::F9,
+];
+
+// Reports a faulting ROM, instead of just closing the window
+fn show_error(error: &ExecutionError, window: &video::Window) {
+    let message = format!("The ROM crashed: {}", error);
+    eprintln!("{}", message);
+    let _ = messagebox::show_simple_message_box(
+        messagebox::MessageBoxFlag::ERROR,
+        "chip8-oxidized",
+        &message,
+        window,
+    );
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn read_movie(path: &str) -> Result<Movie, String> {
+    let data = fs::read(path).map_err(|error| format!("Unable to read {}: {}", path, error))?;
+    Movie::from_bytes(&data).map_err(|error| format!("Unable to load {}: {}", path, error))
+}
+
+// The symbol file given, or the ROM with the extension .sym. Without one, subroutines get labels
+// like sub_2A4.
+fn load_symbols(options: &Options, rom: &Rom, load_address: usize) -> Result<SymbolTable, String> {
+    let path = match &options.symbols {
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(Path::new(&options.rom_path).with_extension("sym")).filter(|p| p.exists()),
+    };
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(SymbolTable::generate(rom.data(), load_address)),
+    };
+    let text = fs::read_to_string(&path)
+        .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+    let symbols = SymbolTable::parse(&text)
+        .map_err(|error| format!("Unable to load {}: {}", path.display(), error))?;
+    println!("Loaded symbols from {}", path.display());
+    Ok(symbols)
+}
+
+type TraceFile = Tracer<BufWriter<File>>;
+
+// Tools watching the machine run, enabled by the options. They are hooks of the core, see
+// SChip8::run_frame_with.
+type Tools = (Option<TraceFile>, Option<Profiler>, Option<Coverage>);
+
+fn create_tools(options: &Options, symbols: &SymbolTable) -> Result<Tools, String> {
+    let tracer = match &options.trace {
+        Some(path) => {
+            let file = File::create(path)
+                .map_err(|error| format!("Unable to create {}: {}", path, error))?;
+            let mut tracer = Tracer::new(BufWriter::new(file));
+            tracer.set_symbols(symbols.clone());
+            if let Some((start, end)) = options.trace_range {
+                tracer.set_range(start, end);
+            }
+            if let Some((first, last)) = options.trace_frames {
+                tracer.set_frames(first, last);
+            }
+            Some(tracer)
+        }
+        None => None,
+    };
+    let profiler = Some(Profiler::new()).filter(|_| options.profile);
+    let coverage = options.coverage.as_ref().map(|_| Coverage::new());
+    Ok((tracer, profiler, coverage))
+}
+
+// Writes the memory map of the coverage as a PNG image, each byte a square of pixels
+fn write_memory_map(path: &str, map: &[u32]) -> Result<(), String> {
+    let error = |error: &dyn std::fmt::Display| format!("Unable to write {}: {}", path, error);
+    let scale = MEMORY_MAP_SCALE;
+    let width = MEMORY_MAP_WIDTH * scale;
+    let mut data = Vec::with_capacity(map.len() * scale * scale * 3);
+    for row in map.chunks(MEMORY_MAP_WIDTH) {
+        let mut line = Vec::with_capacity(width * 3);
+        for color in row.iter() {
+            for _ in 0..scale {
+                line.extend_from_slice(&color.to_be_bytes()[1..]);
+            }
+        }
+        for _ in 0..scale {
+            data.extend_from_slice(&line);
+        }
+    }
+    let height = data.len() / (width * 3);
+
+    let file = File::create(path).map_err(|e| error(&e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| error(&e))?;
+    writer.write_image_data(&data).map_err(|e| error(&e))?;
+    writer.finish().map_err(|e| error(&e))
+}
+
+// Writes the trace and the coverage, and prints and exports the profile
+fn finish_tools(
+    tools: Tools,
+    schip8: &SChip8,
+    rom: &Rom,
+    options: &Options,
+) -> Result<(), io::Error> {
+    let (tracer, profiler, coverage) = tools;
+    if let (Some(tracer), Some(path)) = (tracer, &options.trace) {
+        if let Err(error) = tracer.finish() {
+            println!("Unable to write {}: {}", path, error);
+            return Err(io::Error::other("Other"));
+        }
+        println!("Wrote the trace to {}", path);
+    }
+    if let Some(profiler) = profiler {
+        print!("{}", profiler.report(schip8, PROFILE_REPORT_LINES));
+        if let Some(path) = &options.profile_export {
+            let data = if path.ends_with(".json") {
+                profiler.to_json(schip8)
+            } else {
+                profiler.to_csv(schip8)
+            };
+            if let Err(error) = fs::write(path, data) {
+                println!("Unable to write {}: {}", path, error);
+                return Err(io::Error::other("Other"));
+            }
+            println!("Exported the profile to {}", path);
+        }
+    }
+    if let (Some(coverage), Some(path)) = (coverage, &options.coverage) {
+        let start = schip8.platform().load_address;
+        let program = start..start + rom.size();
+        println!(
+            "{} of the {} byte of the program were used",
+            coverage.used(program.clone()),
+            rom.size()
+        );
+        if let Err(error) = fs::write(path, coverage.to_text()) {
+            println!("Unable to write {}: {}", path, error);
+            return Err(io::Error::other("Other"));
+        }
+        println!("Wrote the coverage to {}", path);
+        if let Some(path) = &options.coverage_map {
+            let map = coverage.memory_map(schip8.memory().len(), program);
+            if let Err(error) = write_memory_map(path, &map) {
+                println!("{}", error);
+                return Err(io::Error::other("Other"));
+            }
+            println!("Wrote the memory map to {}", path);
+        }
+    }
+    Ok(())
+}
+
+// Plays a movie back as fast as possible without a window, then compares the final state to the
+// one recorded, so recorded sessions can serve as regression tests
+fn play_headless(movie: &Movie, schip8: &mut SChip8, tools: &mut Tools) -> Result<(), io::Error> {
+    for frame in movie.frames.iter() {
+        match frame.run_with(schip8, tools) {
+            Ok(frame) if frame.exited => break,
+            Ok(_) => {}
+            Err(error) => {
+                // Part of the recording as well, the state is still compared
+                println!("The ROM crashed: {}", error);
+                break;
+            }
+        }
+    }
+
+    let hash = schip8.state_hash();
+    println!(
+        "Played {} frames, final state: {}",
+        movie.frames.len(),
+        hex(&hash)
+    );
+    match movie.final_hash {
+        Some(expected) if expected == hash => {
+            println!("Final state matches the movie");
+            Ok(())
+        }
+        Some(expected) => {
+            println!("Final state differs from the movie: {}", hex(&expected));
+            Err(io::Error::other("Other"))
+        }
+        None => {
+            println!("The movie has no final state to compare with");
+            Ok(())
         }
     }
 }
@@ -31,16 +263,109 @@ impl audio::AudioCallback for SquareWave {
 fn main() -> Result<(), io::Error> {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() == 1 {
-        println!("Usage: chip8-oxidized <file-path>");
-        return Err(io::Error::new(io::ErrorKind::Other, "Other"));
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(error) => {
+            println!("{}", error);
+            println!("{}", USAGE);
+            return Err(io::Error::other("Other"));
+        }
+    };
+
+    let rom = match Rom::read(&options.rom_path) {
+        Ok(rom) => rom,
+        Err(error) => {
+            println!("Unable to open {}: {}", options.rom_path, error);
+            return Err(io::Error::other("Other"));
+        }
+    };
+    println!("{} is {} byte long", &options.rom_path, rom.size());
+    println!("SHA-1: {}", rom.sha1_hex());
+
+    let movie = match options.play.as_deref().map(read_movie) {
+        Some(Ok(movie)) => Some(movie),
+        Some(Err(error)) => {
+            println!("{}", error);
+            return Err(io::Error::other("Other"));
+        }
+        None => None,
+    };
+
+    // A movie brings its own platform and seed
+    let (platform, seed) = match &movie {
+        Some(movie) => (movie.platform.clone(), movie.seed),
+        None => (
+            options.platform.clone(),
+            options.seed.unwrap_or_else(rand::random),
+        ),
+    };
+    println!("Emulating platform {}", platform.name);
+    // Printed, so a run can be reproduced with --seed
+    println!("Seed: {}", seed);
+
+    let machine = match &movie {
+        Some(movie) => movie.machine(&rom).map_err(|error| error.to_string()),
+        None => SChip8::new(&rom, &platform, Box::new(SeededRandom::new(seed)))
+            .map_err(|error| error.to_string()),
+    };
+    let mut schip8 = match machine {
+        Ok(schip8) => schip8,
+        Err(error) => {
+            println!("Unable to load {}: {}", options.rom_path, error);
+            return Err(io::Error::other("Other"));
+        }
+    };
+
+    let symbols = match load_symbols(&options, &rom, platform.load_address) {
+        Ok(symbols) => symbols,
+        Err(error) => {
+            println!("{}", error);
+            return Err(io::Error::other("Other"));
+        }
+    };
+
+    let mut tools = match create_tools(&options, &symbols) {
+        Ok(tools) => tools,
+        Err(error) => {
+            println!("{}", error);
+            return Err(io::Error::other("Other"));
+        }
+    };
+
+    if let (true, Some(movie)) = (options.headless, &movie) {
+        let result = play_headless(movie, &mut schip8, &mut tools);
+        finish_tools(tools, &schip8, &rom, &options)?;
+        return result;
     }
 
-    let error_message = format!("Unable to open {}", args[1]);
-    let file: Vec<u8> = fs::read(&args[1]).expect(error_message.as_str());
-    println!("{} is {} byte long", &args[1], file.len());
+    // Frames of the movie played back so far
+    let mut played_frames = 0;
+    let mut recording = options
+        .record
+        .as_ref()
+        .map(|_| Movie::new(&rom, &platform, seed));
+    // Loading states would break the recording or playback
+    let movie_active = movie.is_some() || recording.is_some();
+    // Flags left by other runs as well, so movies always start with blank ones
+    if !movie_active {
+        schip8.set_flag_storage(Box::new(FileFlags::new(&rom)));
+    }
 
-    let mut schip8 = SChip8::new(file.clone());
+    let slots = Slots::new(&rom);
+    let mut debug: Option<Box<dyn DebugFrontend>> = if options.debug {
+        Some(Box::new(DebugConsole::new(&schip8, symbols)))
+    } else if let Some(port) = options.gdb {
+        match GdbStub::listen(port) {
+            Ok(stub) => Some(Box::new(stub)),
+            Err(error) => {
+                println!("Unable to serve GDB on port {}: {}", port, error);
+                return Err(io::Error::other("Other"));
+            }
+        }
+    } else {
+        None
+    };
+    let mut rewind = Rewind::new(REWIND_FRAMES);
 
     let sdl2_context = sdl2::init().expect("Failed to initialize SDL");
     let sdl2_audio_system = sdl2_context.audio().unwrap();
@@ -79,21 +404,23 @@ fn main() -> Result<(), io::Error> {
         (Keycode::C, 0xC),
         (Keycode::D, 0xD),
         (Keycode::E, 0xE),
-        (Keycode::F, 0xF)
-    ].iter().cloned().collect();
+        (Keycode::F, 0xF),
+    ]
+    .iter()
+    .cloned()
+    .collect();
 
     let spec = audio::AudioSpecDesired {
         channels: Some(1),
         freq: Some(44100),
         samples: None,
     };
-    let audio_device = sdl2_audio_system
-        .open_playback(None, &spec, |spec| {
-            return SquareWave {
-                phase: 0.0,
-                phase_inc: 440.0 / spec.freq as f32,
-                volume: 0.10,
-            };
+    let mut audio_device = sdl2_audio_system
+        .open_playback(None, &spec, |spec| PatternWave {
+            audio: schip8.audio().clone(),
+            sample_rate: spec.freq as f32,
+            position: 0.0,
+            volume: 0.10,
         })
         .unwrap();
 
@@ -102,7 +429,7 @@ fn main() -> Result<(), io::Error> {
 
     let window = sdl2_video_system
         .window(
-            ["chip8-oxidized", &args[1]].join(" - ").as_str(),
+            ["chip8-oxidized", &options.rom_path].join(" - ").as_str(),
             window_width,
             window_height,
         )
@@ -119,7 +446,8 @@ fn main() -> Result<(), io::Error> {
 
     let mut event_pump = sdl2_context.event_pump().unwrap();
     let mut redraw = true;
-    let mut key = 0;
+    let mut key = NO_KEY;
+    let mut rewinding = false;
     let mut time = SystemTime::now();
     'running: loop {
         for event in event_pump.poll_iter() {
@@ -128,47 +456,136 @@ fn main() -> Result<(), io::Error> {
                 Quit { .. } => {
                     break 'running;
                 }
-                KeyDown { keycode, .. } => {
-                    if keycode != None {
-                        let code = keycode.unwrap();
-                        key = 0;
-                        match key_bindings.get(&code) {
-                            Some(binding) => {
-                                schip8.key_pad[*binding] = true;
-                                key = *binding;
+                KeyDown {
+                    keycode: Some(code),
+                    keymod,
+                    ..
+                } => {
+                    if let Some(slot) = SLOT_KEYS.iter().position(|&slot_key| slot_key == code) {
+                        let slot = slot + 1;
+                        if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            match slots.save(slot, &schip8) {
+                                Ok(()) => println!("Saved slot {}", slot),
+                                Err(error) => eprintln!("{}", error),
+                            }
+                        } else if movie_active {
+                            eprintln!("Loading slots is not possible during a movie");
+                        } else {
+                            match slots.load(slot, &mut schip8) {
+                                Ok(()) => {
+                                    println!("Loaded slot {}", slot);
+                                    redraw = true;
+                                }
+                                Err(error) => eprintln!("{}", error),
                             }
-                            None => {}
                         }
+                    } else if code == REWIND_KEY && !movie_active {
+                        rewinding = true;
+                    } else if let Some(binding) = key_bindings.get(&code) {
+                        schip8.set_key(*binding, true);
+                        key = *binding;
                     }
                 }
-                KeyUp { keycode, .. } => {
-                    if keycode != None {
-                        let code = keycode.unwrap();
-                        match key_bindings.get(&code) {
-                            Some(binding) => schip8.key_pad[*binding] = false,
-                            None => {}
-                        }
+                KeyUp {
+                    keycode: Some(code),
+                    ..
+                } => {
+                    if code == REWIND_KEY {
+                        rewinding = false;
+                    } else if let Some(binding) = key_bindings.get(&code) {
+                        schip8.set_key(*binding, false);
                     }
                 }
                 _ => {}
             }
         }
 
-        if !schip8.run(key, &mut redraw) {
-            break;
+        if let Some(debug) = &mut debug {
+            if !debug.update(&mut schip8) {
+                break 'running;
+            }
         }
 
+        let paused = debug
+            .as_mut()
+            .is_some_and(|debug| debug.debugger().paused());
         let end = SystemTime::now().duration_since(time).unwrap();
-        if end.as_millis() >= 16 {
-            if schip8.dt > 0 {
-                schip8.dt -= 1;
+        if end.as_millis() >= 16 && rewinding {
+            // Go back one frame per frame, until the history runs out
+            if let Some(snapshot) = rewind.step_back() {
+                if let Err(error) = schip8.restore(snapshot) {
+                    eprintln!("Unable to rewind: {}", error);
+                }
+                redraw = true;
             }
-            if schip8.st > 0 {
-                schip8.st -= 1;
-                audio_device.resume();
-                if schip8.st == 0 {
-                    audio_device.pause();
+            audio_device.pause();
+            time = SystemTime::now();
+        } else if end.as_millis() >= 16 && paused {
+            audio_device.pause();
+            time = SystemTime::now();
+        } else if end.as_millis() >= 16 {
+            // Run one 60 Hz frame, the core executes its instructions and ticks the timers.
+            // While a movie is played back, its input replaces the keyboard.
+            let movie_frame = movie
+                .as_ref()
+                .and_then(|movie| movie.frames.get(played_frames));
+            let result = if let Some(movie_frame) = movie_frame {
+                played_frames += 1;
+                if movie.as_ref().map(|movie| movie.frames.len()) == Some(played_frames) {
+                    println!("Playback finished, the keyboard is active again");
+                }
+                movie_frame.run_with(&mut schip8, &mut tools)
+            } else {
+                if let Some(recording) = &mut recording {
+                    recording.record(&schip8, key);
+                }
+                // The tools come second, so they only see the instructions the debugger lets run
+                let result = match &mut debug {
+                    Some(debug) => schip8.run_frame_with(key, &mut (debug.debugger(), &mut tools)),
+                    None => schip8.run_frame_with(key, &mut tools),
+                };
+                // Hashed right away, keys released after the last recorded frame must not change it
+                if let Some(recording) = &mut recording {
+                    recording.final_hash = Some(schip8.state_hash());
+                }
+                result
+            };
+            let frame = match (result, &mut debug) {
+                (Ok(frame), _) => frame,
+                (Err(error), Some(debug)) => {
+                    debug.crashed(&error, &schip8);
+                    continue 'running;
+                }
+                (Err(error), None) => {
+                    show_error(&error, canvas.window());
+                    break 'running;
+                }
+            };
+            if frame.exited {
+                if let Some(debug) = &mut debug {
+                    debug.exited();
                 }
+                break 'running;
+            }
+            redraw |= frame.redraw;
+            if let (true, Some(debug)) = (frame.stopped, &mut debug) {
+                debug.stopped(&schip8);
+            } else {
+                key = NO_KEY;
+                rewind.push(schip8.snapshot());
+            }
+
+            if frame.sound {
+                // Holding the lock keeps the callback from running, the new pattern starts with the next buffer
+                {
+                    let mut wave = audio_device.lock();
+                    if wave.audio != *schip8.audio() {
+                        wave.audio = schip8.audio().clone();
+                    }
+                }
+                audio_device.resume();
+            } else {
+                audio_device.pause();
             }
             time = SystemTime::now();
         }
@@ -176,29 +593,25 @@ fn main() -> Result<(), io::Error> {
         if redraw {
             canvas.clear();
 
+            let display = schip8.display();
             let mut texture = texture_creator
                 .create_texture_streaming(
                     pixels::PixelFormatEnum::RGB24,
-                    schip8.screen_width as u32,
-                    schip8.screen_height as u32,
+                    display.width() as u32,
+                    display.height() as u32,
                 )
                 .unwrap();
-            let num_pixels = schip8.screen_width * schip8.screen_height;
+            let num_pixels = display.width() * display.height();
             let mut texture_data: Vec<u8> = vec![0; num_pixels * 3];
-            for i in 0..num_pixels {
-                let mut color = 0x00;
-                let pixel = schip8.screen[i];
-
-                if pixel == 1 {
-                    color = 0xFF;
-                }
+            for (i, &pixel) in display.pixels().iter().enumerate() {
+                let (r, g, b) = options.palette.rgb(pixel);
 
-                texture_data[i * 3] = color;
-                texture_data[i * 3 + 1] = color;
-                texture_data[i * 3 + 2] = color;
+                texture_data[i * 3] = r;
+                texture_data[i * 3 + 1] = g;
+                texture_data[i * 3 + 2] = b;
             }
             texture
-                .update(None, &texture_data, (schip8.screen_width * 3) as usize)
+                .update(None, &texture_data, display.width() * 3)
                 .unwrap();
             canvas.copy(&texture, None, None).unwrap();
             canvas.present();
@@ -209,6 +622,17 @@ fn main() -> Result<(), io::Error> {
         // Arbitrarily chosen duration of 1ms
         std::thread::sleep(Duration::from_millis(1));
     }
+    finish_tools(tools, &schip8, &rom, &options)?;
+
+    if let (Some(path), Some(recording)) = (&options.record, recording) {
+        match fs::write(path, recording.to_bytes()) {
+            Ok(()) => println!("Recorded {} frames to {}", recording.frames.len(), path),
+            Err(error) => {
+                println!("Unable to write {}: {}", path, error);
+                return Err(io::Error::other("Other"));
+            }
+        }
+    }
 
-    return Ok(());
+    Ok(())
 }
@@ -0,0 +1,212 @@
+use c8_ox_core::{Palette, Platform};
+
+pub const USAGE: &str = "Usage: chip8-oxidized [--platform <name>] [--speed <instructions per frame>] \
+                         [--load-address <hex>] [--seed <number>] [--palette <RRGGBB,RRGGBB,RRGGBB,RRGGBB>] \
+                         [--record <movie> | --play <movie> [--headless]] [--debug | --gdb <port>] \
+                         [--symbols <file>] \
+                         [--trace <file> [--trace-range <hex>-<hex>] [--trace-frames <first>-<last>]] \
+                         [--profile [--profile-export <file.csv|file.json>]] \
+                         [--coverage <file> [--coverage-map <file.png>]] <file-path>";
+
+// Command line options of the SDL frontend
+pub struct Options {
+    pub rom_path: String,
+    pub platform: Platform,
+    pub palette: Palette,
+    pub seed: Option<u64>,                   // Random when not given
+    pub record: Option<String>,              // Movie file to record the input to
+    pub play: Option<String>,                // Movie file to play back
+    pub headless: bool,                      // Play back without a window and check the final state
+    pub debug: bool,                         // Start paused in the debugger on the terminal
+    pub gdb: Option<u16>,                    // Port to wait for a GDB client on
+    pub symbols: Option<String>,             // Labels for the debugger and the trace
+    pub trace: Option<String>,               // File to log every executed instruction to
+    pub trace_range: Option<(usize, usize)>, // Addresses of the instructions to log
+    pub trace_frames: Option<(u64, u64)>,    // Frames to log, counted from 0
+    pub profile: bool,                       // Print where the instructions were spent on exit
+    pub profile_export: Option<String>,      // File to write the profile to, as JSON or CSV
+    pub coverage: Option<String>, // File to write the bytes executed, read and written to
+    pub coverage_map: Option<String>, // PNG image to draw the coverage to
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut rom_path = None;
+        let mut platform = Platform::default();
+        let mut palette = Palette::default();
+        let mut load_address = None;
+        let mut speed = None;
+        let mut seed = None;
+        let mut record = None;
+        let mut play = None;
+        let mut headless = false;
+        let mut debug = false;
+        let mut gdb = None;
+        let mut symbols = None;
+        let mut trace = None;
+        let mut trace_range = None;
+        let mut trace_frames = None;
+        let mut profile = false;
+        let mut profile_export = None;
+        let mut coverage = None;
+        let mut coverage_map = None;
+
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--platform" => {
+                    let name = args.next().ok_or("--platform expects a name")?;
+                    platform = Platform::from_name(name).ok_or_else(|| {
+                        format!(
+                            "Unknown platform {}, available are: {}",
+                            name,
+                            Platform::NAMES.join(", ")
+                        )
+                    })?;
+                }
+                "--speed" => {
+                    let count = args.next().ok_or("--speed expects a number")?;
+                    // No instructions at all would freeze the program
+                    speed = Some(
+                        count
+                            .parse()
+                            .ok()
+                            .filter(|&speed| speed > 0)
+                            .ok_or_else(|| format!("Invalid speed {}", count))?,
+                    );
+                }
+                "--load-address" => {
+                    let addr = args.next().ok_or("--load-address expects an address")?;
+                    load_address = Some(parse_hex(addr)?);
+                }
+                "--seed" => {
+                    let number = args.next().ok_or("--seed expects a number")?;
+                    seed = Some(
+                        number
+                            .parse()
+                            .map_err(|_| format!("Invalid seed {}", number))?,
+                    );
+                }
+                "--palette" => {
+                    let colors = args.next().ok_or("--palette expects four colours")?;
+                    palette = Palette::parse(colors)
+                        .ok_or_else(|| format!("Invalid palette {}", colors))?;
+                }
+                "--record" => {
+                    record = Some(args.next().ok_or("--record expects a file path")?.clone());
+                }
+                "--play" => {
+                    play = Some(args.next().ok_or("--play expects a file path")?.clone());
+                }
+                "--headless" => headless = true,
+                "--debug" => debug = true,
+                "--gdb" => {
+                    let port = args.next().ok_or("--gdb expects a port")?;
+                    gdb = Some(port.parse().map_err(|_| format!("Invalid port {}", port))?);
+                }
+                "--trace" => {
+                    trace = Some(args.next().ok_or("--trace expects a file path")?.clone());
+                }
+                "--trace-range" => {
+                    let range = args
+                        .next()
+                        .ok_or("--trace-range expects an address range")?;
+                    let (start, end) = range
+                        .split_once('-')
+                        .ok_or_else(|| format!("Invalid address range {}", range))?;
+                    trace_range = Some((parse_hex(start)?, parse_hex(end)?));
+                }
+                "--trace-frames" => {
+                    let range = args.next().ok_or("--trace-frames expects a frame range")?;
+                    let frames = range
+                        .split_once('-')
+                        .and_then(|(first, last)| Some((first.parse().ok()?, last.parse().ok()?)));
+                    trace_frames =
+                        Some(frames.ok_or_else(|| format!("Invalid frame range {}", range))?);
+                }
+                "--profile" => profile = true,
+                "--profile-export" => {
+                    let path = args.next().ok_or("--profile-export expects a file path")?;
+                    profile_export = Some(path.clone());
+                }
+                "--symbols" => {
+                    let path = args.next().ok_or("--symbols expects a file path")?;
+                    symbols = Some(path.clone());
+                }
+                "--coverage" => {
+                    let path = args.next().ok_or("--coverage expects a file path")?;
+                    coverage = Some(path.clone());
+                }
+                "--coverage-map" => {
+                    let path = args.next().ok_or("--coverage-map expects a file path")?;
+                    coverage_map = Some(path.clone());
+                }
+                _ if arg.starts_with("--") => {
+                    return Err(format!("Unknown option {}", arg));
+                }
+                _ => rom_path = Some(arg.clone()),
+            }
+        }
+
+        // Applied last, so they do not depend on the order of the options and --platform
+        if let Some(speed) = speed {
+            platform.speed = speed;
+        }
+        if let Some(load_address) = load_address {
+            if load_address < 0x200 || load_address >= platform.memory_size {
+                return Err(format!("Invalid load address {:X}", load_address));
+            }
+            platform.load_address = load_address;
+        }
+
+        if record.is_some() && play.is_some() {
+            return Err("--record and --play cannot be combined".to_string());
+        }
+        if headless && play.is_none() {
+            return Err("--headless needs a movie to --play".to_string());
+        }
+        // Stopping in the middle of a frame would break the frame by frame input of a movie
+        if (debug || gdb.is_some()) && (record.is_some() || play.is_some()) {
+            return Err("Debugging cannot be combined with movies".to_string());
+        }
+        if debug && gdb.is_some() {
+            return Err("--debug and --gdb cannot be combined".to_string());
+        }
+
+        if (trace_range.is_some() || trace_frames.is_some()) && trace.is_none() {
+            return Err("--trace-range and --trace-frames need a file to --trace to".to_string());
+        }
+        if profile_export.is_some() && !profile {
+            return Err("--profile-export needs --profile".to_string());
+        }
+        if coverage_map.is_some() && coverage.is_none() {
+            return Err("--coverage-map needs a file to write the --coverage to".to_string());
+        }
+
+        Ok(Options {
+            rom_path: rom_path.ok_or("No ROM given")?,
+            platform,
+            palette,
+            seed,
+            record,
+            play,
+            headless,
+            debug,
+            gdb,
+            symbols,
+            trace,
+            trace_range,
+            trace_frames,
+            profile,
+            profile_export,
+            coverage,
+            coverage_map,
+        })
+    }
+}
+
+// Parses a hexadecimal number, with or without 0x prefix
+pub fn parse_hex(number: &str) -> Result<usize, String> {
+    usize::from_str_radix(number.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("Invalid hexadecimal number {}", number))
+}
@@ -0,0 +1,79 @@
+// Files kept per ROM in the user's data directory, named after the SHA-1 of the ROM
+use c8_ox_core::{FlagStorage, Rom, SChip8};
+use std::{fs, path::PathBuf};
+
+// Falls back to the working directory if SDL does not know a data directory
+fn data_directory() -> PathBuf {
+    sdl2::filesystem::pref_path("", "chip8-oxidized")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+}
+
+// Number of quick-save slots, bound to F1 to F9
+pub const NUM_SLOTS: usize = 9;
+
+// Quick-save slots of one ROM, stored as <SHA-1>.<slot>.state
+pub struct Slots {
+    directory: PathBuf,
+    rom_sha1: String,
+}
+
+impl Slots {
+    pub fn new(rom: &Rom) -> Slots {
+        Slots {
+            directory: data_directory(),
+            rom_sha1: rom.sha1_hex(),
+        }
+    }
+
+    fn path(&self, slot: usize) -> PathBuf {
+        self.directory
+            .join(format!("{}.{}.state", self.rom_sha1, slot))
+    }
+
+    pub fn save(&self, slot: usize, schip8: &SChip8) -> Result<(), String> {
+        let path = self.path(slot);
+        fs::write(&path, schip8.snapshot())
+            .map_err(|error| format!("Unable to write {}: {}", path.display(), error))
+    }
+
+    pub fn load(&self, slot: usize, schip8: &mut SChip8) -> Result<(), String> {
+        let path = self.path(slot);
+        let snapshot = fs::read(&path)
+            .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+        schip8
+            .restore(&snapshot)
+            .map_err(|error| format!("Unable to load {}: {}", path.display(), error))
+    }
+}
+
+// RPL user flags of one ROM, stored as <SHA-1>.flags
+pub struct FileFlags {
+    path: PathBuf,
+}
+
+impl FileFlags {
+    pub fn new(rom: &Rom) -> FileFlags {
+        FileFlags {
+            path: data_directory().join(format!("{}.flags", rom.sha1_hex())),
+        }
+    }
+}
+
+impl FlagStorage for FileFlags {
+    fn load(&mut self) -> [u8; 16] {
+        let mut flags = [0; 16];
+        // A missing file just means the ROM never stored any flags
+        if let Ok(data) = fs::read(&self.path) {
+            let len = data.len().min(flags.len());
+            flags[..len].copy_from_slice(&data[..len]);
+        }
+        flags
+    }
+
+    fn save(&mut self, flags: &[u8; 16]) {
+        if let Err(error) = fs::write(&self.path, flags) {
+            eprintln!("Unable to write {}: {}", self.path.display(), error);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Lines};
+use std::{env, process};
+
+const USAGE: &str = "Usage: c8-ox-tracediff [--ignore <name>,<name>,...] <trace> <trace>";
+
+// Lines printed before the divergence
+const CONTEXT_LINES: usize = 3;
+
+// Exit status like cmp: the traces match, they differ, or one could not be read
+const EXIT_SAME: i32 = 0;
+const EXIT_DIFFERENT: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
+// Values of a line by name, written as NAME:VALUE or NAME=VALUE. Names are compared in upper case,
+// other words like the disassembly are skipped.
+fn fields(line: &str) -> Vec<(String, &str)> {
+    line.split_whitespace()
+        .filter_map(|word| word.split_once([':', '=']))
+        .filter(|(name, value)| !name.is_empty() && !value.is_empty())
+        .map(|(name, value)| (name.to_uppercase(), value))
+        .collect()
+}
+
+// Hexadecimal values are compared as numbers, so 0A, 0x0a and A are the same
+fn same_value(a: &str, b: &str) -> bool {
+    let number = |value: &str| {
+        let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+        u64::from_str_radix(digits, 16).ok()
+    };
+    match (number(a), number(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.eq_ignore_ascii_case(b),
+    }
+}
+
+// A trace file read line by line, skipping lines without values like headers
+struct Trace<R> {
+    path: String,
+    lines: Lines<R>,
+    number: usize,            // Number of the last line read, starting at 1
+    recent: VecDeque<String>, // The lines before the last one, for context
+    last: Option<String>,     // The last line read
+}
+
+impl Trace<BufReader<File>> {
+    fn open(path: &str) -> Result<Trace<BufReader<File>>, io::Error> {
+        Ok(Trace::new(path, BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> Trace<R> {
+    fn new(path: &str, reader: R) -> Trace<R> {
+        Trace {
+            path: path.to_string(),
+            lines: reader.lines(),
+            number: 0,
+            recent: VecDeque::new(),
+            last: None,
+        }
+    }
+
+    // None at the end of the file
+    fn next_line(&mut self) -> Result<Option<String>, String> {
+        loop {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(error)) => return Err(format!("Unable to read {}: {}", self.path, error)),
+                None => return Ok(None),
+            };
+            self.number += 1;
+            if fields(&line).is_empty() {
+                continue;
+            }
+            if let Some(last) = self.last.replace(line.clone()) {
+                self.recent.push_back(last);
+                if self.recent.len() > CONTEXT_LINES {
+                    self.recent.pop_front();
+                }
+            }
+            return Ok(Some(line));
+        }
+    }
+}
+
+// Names of the values that differ, empty if the lines match. Values only one of the lines has are
+// not compared, so traces of emulators logging less than others can still be compared.
+fn differences(a: &str, b: &str, ignored: &[String]) -> Vec<String> {
+    let a_fields = fields(a);
+    let b_fields = fields(b);
+    let mut common = 0;
+    let mut differences = Vec::new();
+    for (name, a_value) in a_fields.iter() {
+        if ignored.contains(name) {
+            continue;
+        }
+        if let Some((_, b_value)) = b_fields.iter().find(|(other, _)| other == name) {
+            common += 1;
+            if !same_value(a_value, b_value) {
+                differences.push(format!("{} is {} and {}", name, a_value, b_value));
+            }
+        }
+    }
+    if common == 0 {
+        differences.push("the lines have no values in common".to_string());
+    }
+    differences
+}
+
+// One trace ended while the other one continues with line
+fn ended<A, B>(ended: &Trace<A>, longer: &Trace<B>, line: &str, count: usize) -> String {
+    format!(
+        "{} ends after {} instructions, {} continues in line {}:\n  {}\n",
+        ended.path, count, longer.path, longer.number, line
+    )
+}
+
+// Compares the traces up to the first divergence, returns the exit status and what to print.
+// The report of EXIT_ERROR goes to stderr, the others to stdout.
+fn compare<A: BufRead, B: BufRead>(
+    mut a: Trace<A>,
+    mut b: Trace<B>,
+    ignored: &[String],
+) -> (i32, String) {
+    let mut count = 0;
+    loop {
+        let lines = a
+            .next_line()
+            .and_then(|a_line| Ok((a_line, b.next_line()?)));
+        let (a_line, b_line) = match lines {
+            Ok((Some(a_line), Some(b_line))) => (a_line, b_line),
+            Ok((None, None)) => break,
+            Ok((Some(line), None)) => return (EXIT_DIFFERENT, ended(&b, &a, &line, count)),
+            Ok((None, Some(line))) => return (EXIT_DIFFERENT, ended(&a, &b, &line, count)),
+            Err(error) => return (EXIT_ERROR, error),
+        };
+
+        let differences = differences(&a_line, &b_line, ignored);
+        if !differences.is_empty() {
+            let mut report = format!(
+                "The traces diverge at instruction {}, line {} of {} and line {} of {}: {}\n",
+                count + 1,
+                a.number,
+                a.path,
+                b.number,
+                b.path,
+                differences.join(", ")
+            );
+            for line in a.recent.iter() {
+                report += &format!("    {}\n", line);
+            }
+            report += &format!("  < {}\n  > {}\n", a_line, b_line);
+            return (EXIT_DIFFERENT, report);
+        }
+        count += 1;
+    }
+    (
+        EXIT_SAME,
+        format!("The traces match, {} instructions\n", count),
+    )
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut ignored = Vec::new();
+    let mut paths = Vec::new();
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--ignore" => match iter.next() {
+                Some(names) => ignored.extend(names.split(',').map(|name| name.to_uppercase())),
+                None => {
+                    eprintln!("--ignore expects names like DT,ST");
+                    process::exit(EXIT_ERROR);
+                }
+            },
+            _ if arg.starts_with("--") => {
+                eprintln!("Unknown option {}\n{}", arg, USAGE);
+                process::exit(EXIT_ERROR);
+            }
+            _ => paths.push(arg.clone()),
+        }
+    }
+    if paths.len() != 2 {
+        eprintln!("{}", USAGE);
+        process::exit(EXIT_ERROR);
+    }
+
+    let open = |path: &str| {
+        Trace::open(path).unwrap_or_else(|error| {
+            eprintln!("Unable to open {}: {}", path, error);
+            process::exit(EXIT_ERROR);
+        })
+    };
+    let (status, report) = compare(open(&paths[0]), open(&paths[1]), &ignored);
+    if status == EXIT_ERROR {
+        eprint!("{}", report);
+    } else {
+        print!("{}", report);
+    }
+    process::exit(status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE: &str = "\
+; header without values
+PC:0200 OP:00E0 CLS V0:00 I:0000 DT:00
+PC:0202 OP:6005 LD V0, #05 V0:00 I:0000 DT:00
+PC:0204 OP:A300 LD I, #300 V0:05 I:0000 DT:00
+PC:0206 OP:1206 JP #206 V0:05 I:0300 DT:00
+";
+
+    fn compare_text(a: &str, b: &str, ignored: &[&str]) -> (i32, String) {
+        let ignored: Vec<String> = ignored.iter().map(|name| name.to_string()).collect();
+        compare(
+            Trace::new("a.log", a.as_bytes()),
+            Trace::new("b.log", b.as_bytes()),
+            &ignored,
+        )
+    }
+
+    #[test]
+    fn identical_traces_match() {
+        assert_eq!(
+            compare_text(TRACE, TRACE, &[]),
+            (EXIT_SAME, "The traces match, 4 instructions\n".to_string())
+        );
+        // Another spelling of the same values
+        let other = TRACE.replace("PC:", "pc=0x").replace("I:0300", "I:300");
+        assert_eq!(compare_text(TRACE, &other, &[]).0, EXIT_SAME);
+    }
+
+    #[test]
+    fn reports_the_first_differing_value() {
+        let other = TRACE.replace("V0:05 I:0000", "V0:06 I:0000");
+        let (status, report) = compare_text(TRACE, &other, &[]);
+        assert_eq!(status, EXIT_DIFFERENT);
+        assert_eq!(
+            report,
+            "The traces diverge at instruction 3, line 4 of a.log and line 4 of b.log: V0 is 05 and 06\n\
+             \x20   PC:0200 OP:00E0 CLS V0:00 I:0000 DT:00\n\
+             \x20   PC:0202 OP:6005 LD V0, #05 V0:00 I:0000 DT:00\n\
+             \x20 < PC:0204 OP:A300 LD I, #300 V0:05 I:0000 DT:00\n\
+             \x20 > PC:0204 OP:A300 LD I, #300 V0:06 I:0000 DT:00\n"
+        );
+    }
+
+    #[test]
+    fn reports_a_shorter_trace() {
+        let shorter: String = TRACE
+            .lines()
+            .take(3)
+            .map(|line| line.to_string() + "\n")
+            .collect();
+        let (status, report) = compare_text(&shorter, TRACE, &[]);
+        assert_eq!(status, EXIT_DIFFERENT);
+        assert_eq!(
+            report,
+            "a.log ends after 2 instructions, b.log continues in line 4:\n  \
+             PC:0204 OP:A300 LD I, #300 V0:05 I:0000 DT:00\n"
+        );
+        let (status, report) = compare_text(TRACE, &shorter, &[]);
+        assert_eq!(status, EXIT_DIFFERENT);
+        assert!(report.starts_with("b.log ends after 2 instructions"));
+    }
+
+    #[test]
+    fn skips_ignored_values() {
+        let other = TRACE.replace("DT:00", "DT:3C");
+        assert_eq!(compare_text(TRACE, &other, &[]).0, EXIT_DIFFERENT);
+        assert_eq!(compare_text(TRACE, &other, &["DT"]).0, EXIT_SAME);
+
+        // Lines left with nothing to compare differ
+        let (status, report) = compare_text("DT:00\n", "DT:01\n", &["DT"]);
+        assert_eq!(status, EXIT_DIFFERENT);
+        assert!(report.ends_with("the lines have no values in common\n  < DT:00\n  > DT:01\n"));
+    }
+
+    #[test]
+    fn fails_on_unreadable_traces() {
+        let ignored = Vec::new();
+        let (status, report) = compare(
+            Trace::new("a.log", TRACE.as_bytes()),
+            Trace::new("b.log", &b"PC:0200\n\xFF\n"[..]),
+            &ignored,
+        );
+        assert_eq!(status, EXIT_ERROR);
+        assert!(report.starts_with("Unable to read b.log"));
+    }
+}
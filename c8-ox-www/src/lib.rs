@@ -1 +1,140 @@
-mod schip8;
+use c8_ox_core::{FlagStorage, Palette, Platform, Rom, SeededRandom};
+use js_sys::Math;
+use wasm_bindgen::prelude::*;
+
+// RPL user flags kept in the localStorage of the browser, as hex string under a key per ROM
+struct LocalStorageFlags {
+    key: String,
+}
+
+impl LocalStorageFlags {
+    fn storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+}
+
+impl FlagStorage for LocalStorageFlags {
+    fn load(&mut self) -> [u8; 16] {
+        let mut flags = [0; 16];
+        let stored =
+            LocalStorageFlags::storage().and_then(|storage| storage.get_item(&self.key).ok()?);
+        if let Some(stored) = stored {
+            for (i, flag) in flags.iter_mut().enumerate() {
+                *flag = stored
+                    .get(i * 2..i * 2 + 2)
+                    .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                    .unwrap_or(0);
+            }
+        }
+        flags
+    }
+
+    fn save(&mut self, flags: &[u8; 16]) {
+        let hex: String = flags.iter().map(|b| format!("{:02x}", b)).collect();
+        if let Some(storage) = LocalStorageFlags::storage() {
+            let _ = storage.set_item(&self.key, &hex);
+        }
+    }
+}
+
+// Outcome of SChip8::run_frame
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct Frame {
+    pub redraw: bool,
+    pub sound: bool,
+    pub exited: bool,
+}
+
+// Thin adapter exposing the core interpreter to JS
+#[wasm_bindgen]
+pub struct SChip8 {
+    schip8: c8_ox_core::SChip8,
+    palette: Palette,
+}
+
+#[wasm_bindgen]
+impl SChip8 {
+    // The platform is one of the names in Platform::NAMES, e.g. "schip-1.1".
+    // Without a seed for CXNN one is taken from the JS Math object.
+    // The palette is given like "000000,FFFFFF,AAAAAA,555555", see Palette::parse.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        program: Vec<u8>,
+        platform: &str,
+        seed: Option<u32>,
+        palette: Option<String>,
+    ) -> Result<SChip8, JsValue> {
+        let platform = Platform::from_name(platform).ok_or_else(|| {
+            JsValue::from_str(&format!(
+                "Unknown platform {}, available are: {}",
+                platform,
+                Platform::NAMES.join(", ")
+            ))
+        })?;
+        let palette = match palette {
+            Some(colors) => Palette::parse(&colors)
+                .ok_or_else(|| JsValue::from_str(&format!("Invalid palette {}", colors)))?,
+            None => Palette::default(),
+        };
+        let rom = Rom::new(program).map_err(|error| JsValue::from_str(&error.to_string()))?;
+        #[allow(unused_unsafe)] // since Rust analyzer keeps complaining
+        let seed = seed.unwrap_or_else(|| unsafe { (Math::random() * 4294967296.0) as u32 });
+        let random = Box::new(SeededRandom::new(seed as u64));
+        let mut schip8 = c8_ox_core::SChip8::new(&rom, &platform, random)
+            .map_err(|error| JsValue::from_str(&error.to_string()))?;
+        schip8.set_flag_storage(Box::new(LocalStorageFlags {
+            key: format!("chip8-oxidized-flags-{}", rom.sha1_hex()),
+        }));
+        Ok(SChip8 { schip8, palette })
+    }
+
+    // Runs the instructions of one 60 Hz frame and ticks the timers,
+    // throws the description of the fault if the ROM crashed
+    pub fn run_frame(&mut self, key: usize) -> Result<Frame, JsValue> {
+        let frame = self
+            .schip8
+            .run_frame(key)
+            .map_err(|error| JsValue::from_str(&error.to_string()))?;
+        Ok(Frame {
+            redraw: frame.redraw,
+            sound: frame.sound,
+            exited: frame.exited,
+        })
+    }
+
+    pub fn set_key(&mut self, key: usize, status: bool) {
+        self.schip8.set_key(key, status);
+    }
+
+    // Colour index (0 to 3) of a pixel
+    pub fn get_pixel(&self, x: usize, y: usize) -> u8 {
+        self.schip8.display().pixel(x, y)
+    }
+
+    // Colour of a colour index as CSS hex string like "#AAAAAA"
+    pub fn color(&self, index: u8) -> String {
+        let (r, g, b) = self.palette.rgb(index);
+        format!("#{:02X}{:02X}{:02X}", r, g, b)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dt(&self) -> u8 {
+        self.schip8.dt()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn st(&self) -> u8 {
+        self.schip8.st()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn screen_width(&self) -> usize {
+        self.schip8.display().width()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn screen_height(&self) -> usize {
+        self.schip8.display().height()
+    }
+}
@@ -2,10 +2,8 @@
 
 import * as wasm from "c8_ox_www";
 
-// Timing
-const DELAY = 1;
-// How often the game should be updated in one tick
-const REPEAT = 5;
+// Length of one emulated frame in ms, the core ticks its timers once per frame
+const FRAME_TIME = 1000 / 60;
 // Keys
 const KEY_MAP = {
     48: 0x0,
@@ -36,9 +34,11 @@ const ROMPATH_PREFIX = "roms/";
 
 // Load rom
 let rom = null;
+let platform = null;
 
 function init() {
     let romPath = prompt("Enter ROM title");
+    platform = prompt("Enter platform (cosmac-vip, chip-48, schip-1.0, schip-1.1, modern, xo-chip)", "schip-1.1");
 
     let xhr = new XMLHttpRequest();
     xhr.open("GET", (ROMPATH_PREFIX + romPath).trim(), true);
@@ -63,9 +63,11 @@ let schip8 = null;
 let canvas = document.getElementById("canvas");
 let context = canvas.getContext("2d");
 let audio = document.getElementById("audio");
-let begin = window.performance.now();
+let last_time = 0;
+let lag = 0;
 let last_key = 0x10;
-let interval = 0;
+// Colours of the four colour indices (background, plane 1, plane 2, both planes)
+let colors = [];
 
 // Add event listeners
 function keydown(e) {
@@ -83,39 +85,69 @@ window.addEventListener("keydown", keydown);
 window.addEventListener("keyup", keyup);
 
 function start() {
-    schip8 = new wasm.SChip8(rom);
-    interval = window.setInterval(function() {
-        for (let i = 0; i < 5; i++) {
-            run();
-        }
-        window.requestAnimationFrame(render);
-    }, DELAY);
+    try {
+        // A fixed seed for CXNN can be given as ?seed=<number> to reproduce a run,
+        // other colours as ?palette=<background>,<plane 1>,<plane 2>,<both planes>
+        let params = new URLSearchParams(window.location.search);
+        let seed = params.get("seed");
+        let palette = params.get("palette");
+        schip8 = new wasm.SChip8(
+            rom,
+            platform.trim(),
+            seed === null ? undefined : parseInt(seed),
+            palette === null ? undefined : palette
+        );
+        colors = [0, 1, 2, 3].map(index => schip8.color(index));
+    }
+    catch (e) {
+        alert(e);
+        init();
+        return;
+    }
+    last_time = window.performance.now();
+    lag = 0;
+    window.requestAnimationFrame(run);
 }
 
-// Emulator loop
-function run() {
-    let con = schip8.run(last_key);
-    last_key = 0;
-    window.performance.now();
+// Emulator loop, runs as many frames as fit into the time passed since the last call
+function run(time) {
+    // Do not try to catch up after the tab was in the background
+    lag = Math.min(lag + time - last_time, 10 * FRAME_TIME);
+    last_time = time;
 
-    let end = window.performance.now();
-    if (end - begin >= 16) {
-        if (schip8.dt > 0) {
-            schip8.dt -= 1;
+    let redraw = false;
+    while (lag >= FRAME_TIME) {
+        lag -= FRAME_TIME;
+
+        let frame = null;
+        try {
+            frame = schip8.run_frame(last_key);
+        }
+        catch (e) {
+            audio.pause();
+            alert("The ROM crashed: " + e);
+            return;
         }
-        if (schip8.st > 0) {
-            schip8.st -= 1;
+        last_key = 0x10;
+
+        if (frame.sound) {
             audio.play();
-            if (schip8.st == 0) {
-                audio.pause();
-            }
         }
-        begin = window.performance.now();
+        else {
+            audio.pause();
+        }
+        if (frame.exited) {
+            audio.pause();
+            render();
+            return;
+        }
+        redraw = redraw || frame.redraw;
     }
 
-    if (!con) {
-        window.cancelInterval(interval);
+    if (redraw) {
+        render();
     }
+    window.requestAnimationFrame(run);
 }
 
 function render() {
@@ -124,14 +156,7 @@ function render() {
 
     for (let y = 0; y < schip8.screen_height; y++) {
         for (let x = 0; x < schip8.screen_width; x++) {
-            let pixel = schip8.get_pixel(x, y);
-    
-            if (pixel == 0) {
-                context.fillStyle = "black";
-            }
-            else {
-                context.fillStyle = "white";
-            }
+            context.fillStyle = colors[schip8.get_pixel(x, y)];
 
             context.fillRect(
                 x * pixel_w,